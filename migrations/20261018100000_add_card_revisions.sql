@@ -0,0 +1,17 @@
+-- Field-level change log for card edits made through /card/update
+CREATE TABLE IF NOT EXISTS card_revisions (
+    revision_id TEXT NOT NULL,
+    card_id TEXT NOT NULL,
+    field_name TEXT NOT NULL,
+    old_value TEXT NOT NULL,
+    new_value TEXT NOT NULL,
+    changed_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+
+    PRIMARY KEY (revision_id, field_name),
+    FOREIGN KEY (card_id)
+        REFERENCES cards (card_id)
+        ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_card_revisions_card_id_changed_at
+    ON card_revisions (card_id, changed_at DESC);
@@ -0,0 +1,6 @@
+-- Tombstone for soft-deleted cards; purged for good once the retention window passes
+ALTER TABLE cards ADD COLUMN deleted_at DATETIME;
+
+CREATE INDEX IF NOT EXISTS idx_cards_deleted_at
+    ON cards (deleted_at)
+    WHERE deleted_at IS NOT NULL;
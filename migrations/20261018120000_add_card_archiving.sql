@@ -0,0 +1,2 @@
+-- Archived cards are closed or unused but keep their history and stay readable
+ALTER TABLE cards ADD COLUMN archived_at DATETIME;
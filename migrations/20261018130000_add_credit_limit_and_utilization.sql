@@ -0,0 +1,13 @@
+-- Optional credit limit per card, used to compute utilization
+ALTER TABLE cards ADD COLUMN credit_limit REAL;
+
+-- Utilization percentages that raise a warning when a new total due crosses them
+CREATE TABLE IF NOT EXISTS card_utilization_thresholds (
+    card_id TEXT NOT NULL,
+    threshold_percent REAL NOT NULL CHECK (threshold_percent > 0 AND threshold_percent <= 100),
+
+    PRIMARY KEY (card_id, threshold_percent),
+    FOREIGN KEY (card_id)
+        REFERENCES cards (card_id)
+        ON DELETE CASCADE
+);
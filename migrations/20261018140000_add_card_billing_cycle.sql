@@ -0,0 +1,4 @@
+-- Billing cycle: statement day plus either a fixed due day or a days-after-statement offset
+ALTER TABLE cards ADD COLUMN statement_day INTEGER CHECK (statement_day BETWEEN 1 AND 31);
+ALTER TABLE cards ADD COLUMN payment_due_day INTEGER CHECK (payment_due_day BETWEEN 1 AND 31);
+ALTER TABLE cards ADD COLUMN payment_due_offset_days INTEGER CHECK (payment_due_offset_days BETWEEN 1 AND 60);
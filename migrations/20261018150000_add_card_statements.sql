@@ -0,0 +1,34 @@
+-- Closed billing cycles: running-state snapshot plus a copy of the cycle's events
+CREATE TABLE IF NOT EXISTS card_statements (
+    statement_id TEXT PRIMARY KEY,
+    card_id TEXT NOT NULL,
+    cycle_start DATETIME NOT NULL,
+    cycle_end DATETIME NOT NULL,
+    closing_total_due REAL NOT NULL,
+    closing_delta REAL NOT NULL,
+    due_date DATE,
+    payment_status TEXT NOT NULL DEFAULT 'unpaid'
+        CHECK (payment_status IN ('unpaid', 'partially_paid', 'paid')),
+    amount_paid REAL NOT NULL DEFAULT 0,
+    paid_at DATETIME,
+    closed_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+
+    FOREIGN KEY (card_id)
+        REFERENCES cards (card_id)
+        ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_card_statements_card_id_cycle_end
+    ON card_statements (card_id, cycle_end DESC);
+
+CREATE TABLE IF NOT EXISTS card_statement_events (
+    statement_id TEXT NOT NULL,
+    transaction_id TEXT NOT NULL,
+    total_due_input REAL NOT NULL,
+    timestamp DATETIME NOT NULL,
+
+    PRIMARY KEY (statement_id, transaction_id),
+    FOREIGN KEY (statement_id)
+        REFERENCES card_statements (statement_id)
+        ON DELETE CASCADE
+);
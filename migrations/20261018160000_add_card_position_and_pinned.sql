@@ -0,0 +1,13 @@
+-- User-defined carousel order; pinned cards sort ahead of everything else
+ALTER TABLE cards ADD COLUMN position INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE cards ADD COLUMN pinned BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- Keep the current (insertion) order for existing cards
+UPDATE cards
+SET position = (
+    SELECT COUNT(*) FROM cards AS earlier
+    WHERE earlier.user_id = cards.user_id AND earlier.rowid < cards.rowid
+);
+
+CREATE INDEX IF NOT EXISTS idx_cards_user_id_order
+    ON cards (user_id, pinned DESC, position);
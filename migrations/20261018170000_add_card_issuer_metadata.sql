@@ -0,0 +1,8 @@
+-- Optional issuer details to tell similar cards apart. Only the last four
+-- digits of the card number are ever stored.
+ALTER TABLE cards ADD COLUMN network TEXT
+    CHECK (network IN ('visa', 'mastercard', 'rupay', 'amex'));
+ALTER TABLE cards ADD COLUMN last_four TEXT
+    CHECK (last_four IS NULL OR (length(last_four) = 4 AND last_four NOT GLOB '*[^0-9]*'));
+ALTER TABLE cards ADD COLUMN expiry_month INTEGER CHECK (expiry_month BETWEEN 1 AND 12);
+ALTER TABLE cards ADD COLUMN expiry_year INTEGER;
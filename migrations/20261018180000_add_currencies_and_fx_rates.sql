@@ -0,0 +1,15 @@
+-- ISO 4217 currency per card and a home currency per user for combined totals
+ALTER TABLE cards ADD COLUMN currency TEXT NOT NULL DEFAULT 'INR' CHECK (length(currency) = 3);
+ALTER TABLE users ADD COLUMN home_currency TEXT NOT NULL DEFAULT 'INR' CHECK (length(home_currency) = 3);
+
+-- Locally maintained exchange rates: 1 base_currency = rate quote_currency
+CREATE TABLE IF NOT EXISTS fx_rates (
+    base_currency TEXT NOT NULL,
+    quote_currency TEXT NOT NULL,
+    rate REAL NOT NULL CHECK (rate > 0),
+    as_of DATE NOT NULL,
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+
+    PRIMARY KEY (base_currency, quote_currency),
+    CHECK (base_currency <> quote_currency)
+);
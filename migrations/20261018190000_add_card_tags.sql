@@ -0,0 +1,30 @@
+-- User-defined tags (e.g. "personal", "business"), many-to-many with cards
+CREATE TABLE IF NOT EXISTS tags (
+    tag_id TEXT PRIMARY KEY,
+    user_id TEXT NOT NULL,
+    name TEXT NOT NULL,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+
+    FOREIGN KEY (user_id)
+        REFERENCES users (user_id)
+        ON DELETE CASCADE
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_tags_user_id_name
+    ON tags (user_id, name COLLATE NOCASE);
+
+CREATE TABLE IF NOT EXISTS card_tags (
+    card_id TEXT NOT NULL,
+    tag_id TEXT NOT NULL,
+
+    PRIMARY KEY (card_id, tag_id),
+    FOREIGN KEY (card_id)
+        REFERENCES cards (card_id)
+        ON DELETE CASCADE,
+    FOREIGN KEY (tag_id)
+        REFERENCES tags (tag_id)
+        ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_card_tags_tag_id
+    ON card_tags (tag_id);
@@ -0,0 +1,65 @@
+-- Catalog of card issuers with preset colors and an optional logo asset
+CREATE TABLE IF NOT EXISTS issuers (
+    issuer_id TEXT PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE COLLATE NOCASE,
+    primary_color INTEGER NOT NULL,
+    secondary_color INTEGER NOT NULL,
+    logo_path TEXT,
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Every spelling that should resolve to an issuer, including its own name.
+-- alias_key is the lower-cased alias with spaces, dots and hyphens removed.
+CREATE TABLE IF NOT EXISTS issuer_aliases (
+    alias_key TEXT PRIMARY KEY,
+    alias TEXT NOT NULL,
+    issuer_id TEXT NOT NULL,
+    is_name BOOLEAN NOT NULL DEFAULT FALSE,
+
+    FOREIGN KEY (issuer_id)
+        REFERENCES issuers (issuer_id)
+        ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_issuer_aliases_issuer_id
+    ON issuer_aliases (issuer_id);
+
+ALTER TABLE cards ADD COLUMN issuer_id TEXT REFERENCES issuers (issuer_id) ON DELETE SET NULL;
+
+INSERT INTO issuers (issuer_id, name, primary_color, secondary_color) VALUES
+    ('hdfc', 'HDFC Bank', 0x004C8F, 0xED232A),
+    ('icici', 'ICICI Bank', 0xAE282E, 0xF37E20),
+    ('sbi', 'State Bank of India', 0x280071, 0x00B5EF),
+    ('axis', 'Axis Bank', 0x97144D, 0xEDEDED),
+    ('kotak', 'Kotak Mahindra Bank', 0xED1C24, 0x003874),
+    ('amex', 'American Express', 0x006FCF, 0xFFFFFF),
+    ('chase', 'Chase', 0x117ACA, 0x0F2C52),
+    ('citi', 'Citi', 0x003B70, 0xD71E28);
+
+INSERT INTO issuer_aliases (alias_key, alias, issuer_id, is_name) VALUES
+    ('hdfcbank', 'HDFC Bank', 'hdfc', TRUE),
+    ('hdfc', 'HDFC', 'hdfc', FALSE),
+    ('icicibank', 'ICICI Bank', 'icici', TRUE),
+    ('icici', 'ICICI', 'icici', FALSE),
+    ('statebankofindia', 'State Bank of India', 'sbi', TRUE),
+    ('sbi', 'SBI', 'sbi', FALSE),
+    ('sbicard', 'SBI Card', 'sbi', FALSE),
+    ('axisbank', 'Axis Bank', 'axis', TRUE),
+    ('axis', 'Axis', 'axis', FALSE),
+    ('kotakmahindrabank', 'Kotak Mahindra Bank', 'kotak', TRUE),
+    ('kotak', 'Kotak', 'kotak', FALSE),
+    ('kotakbank', 'Kotak Bank', 'kotak', FALSE),
+    ('americanexpress', 'American Express', 'amex', TRUE),
+    ('amex', 'Amex', 'amex', FALSE),
+    ('chase', 'Chase', 'chase', TRUE),
+    ('jpmorganchase', 'JPMorgan Chase', 'chase', FALSE),
+    ('citi', 'Citi', 'citi', TRUE),
+    ('citibank', 'Citibank', 'citi', FALSE);
+
+-- Link existing free-text banks through the same normalization as alias_key
+UPDATE cards
+SET issuer_id = (
+    SELECT a.issuer_id FROM issuer_aliases a
+    WHERE a.alias_key = replace(replace(replace(lower(trim(cards.card_bank)), ' ', ''), '.', ''), '-', '')
+)
+WHERE issuer_id IS NULL;
@@ -0,0 +1,14 @@
+-- Themes extend the packed RGB columns rather than replacing them, so older
+-- clients reading card_primary_color / card_secondary_color keep working.
+-- NULL alpha means opaque.
+ALTER TABLE cards ADD COLUMN card_primary_alpha INTEGER CHECK (card_primary_alpha BETWEEN 0 AND 254);
+ALTER TABLE cards ADD COLUMN card_secondary_alpha INTEGER CHECK (card_secondary_alpha BETWEEN 0 AND 254);
+ALTER TABLE cards ADD COLUMN gradient_angle INTEGER NOT NULL DEFAULT 135 CHECK (gradient_angle BETWEEN 0 AND 359);
+ALTER TABLE cards ADD COLUMN theme_preset TEXT;
+
+-- New cards in these banks default to the catalog colors, which must now pass
+-- the readability check. Leave them alone if an admin has already changed them.
+UPDATE issuers SET secondary_color = 0x0072BC, updated_at = CURRENT_TIMESTAMP
+WHERE issuer_id = 'sbi' AND secondary_color = 0x00B5EF;
+UPDATE issuers SET secondary_color = 0xC1325F, updated_at = CURRENT_TIMESTAMP
+WHERE issuer_id = 'axis' AND secondary_color = 0xEDEDED;
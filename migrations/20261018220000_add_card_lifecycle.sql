@@ -0,0 +1,11 @@
+-- Lifecycle state of a card. status_changed_at stays NULL until the first
+-- change; each change is also recorded in card_revisions.
+ALTER TABLE cards ADD COLUMN status TEXT NOT NULL DEFAULT 'active'
+    CHECK (status IN ('active', 'frozen', 'lost', 'closed'));
+ALTER TABLE cards ADD COLUMN status_changed_at DATETIME;
+
+-- The card that took over after a reissue. A card has at most one successor
+-- and one predecessor, so replacements form simple chains.
+ALTER TABLE cards ADD COLUMN replaced_by TEXT REFERENCES cards (card_id) ON DELETE SET NULL;
+CREATE UNIQUE INDEX IF NOT EXISTS idx_cards_replaced_by
+    ON cards (replaced_by) WHERE replaced_by IS NOT NULL;
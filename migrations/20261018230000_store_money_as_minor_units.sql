@@ -0,0 +1,45 @@
+-- Money moves from REAL major units to INTEGER minor units (hundredths), so
+-- 12345.67 is stored as 1234567 and sums and deltas are exact. Each column is
+-- rebuilt in place: add the integer column, convert, drop the old one and take
+-- over its name. card_events.total_due_input was declared INTEGER but has been
+-- holding floats, so it is converted the same way.
+
+ALTER TABLE card_running_state ADD COLUMN last_total_due_minor INTEGER NOT NULL DEFAULT 0;
+UPDATE card_running_state SET last_total_due_minor = CAST(ROUND(last_total_due * 100) AS INTEGER);
+ALTER TABLE card_running_state DROP COLUMN last_total_due;
+ALTER TABLE card_running_state RENAME COLUMN last_total_due_minor TO last_total_due;
+
+ALTER TABLE card_running_state ADD COLUMN last_delta_minor INTEGER NOT NULL DEFAULT 0;
+UPDATE card_running_state SET last_delta_minor = CAST(ROUND(last_delta * 100) AS INTEGER);
+ALTER TABLE card_running_state DROP COLUMN last_delta;
+ALTER TABLE card_running_state RENAME COLUMN last_delta_minor TO last_delta;
+
+ALTER TABLE card_events ADD COLUMN total_due_input_minor INTEGER NOT NULL DEFAULT 0;
+UPDATE card_events SET total_due_input_minor = CAST(ROUND(total_due_input * 100) AS INTEGER);
+ALTER TABLE card_events DROP COLUMN total_due_input;
+ALTER TABLE card_events RENAME COLUMN total_due_input_minor TO total_due_input;
+
+ALTER TABLE cards ADD COLUMN credit_limit_minor INTEGER;
+UPDATE cards SET credit_limit_minor = CAST(ROUND(credit_limit * 100) AS INTEGER) WHERE credit_limit IS NOT NULL;
+ALTER TABLE cards DROP COLUMN credit_limit;
+ALTER TABLE cards RENAME COLUMN credit_limit_minor TO credit_limit;
+
+ALTER TABLE card_statements ADD COLUMN closing_total_due_minor INTEGER NOT NULL DEFAULT 0;
+UPDATE card_statements SET closing_total_due_minor = CAST(ROUND(closing_total_due * 100) AS INTEGER);
+ALTER TABLE card_statements DROP COLUMN closing_total_due;
+ALTER TABLE card_statements RENAME COLUMN closing_total_due_minor TO closing_total_due;
+
+ALTER TABLE card_statements ADD COLUMN closing_delta_minor INTEGER NOT NULL DEFAULT 0;
+UPDATE card_statements SET closing_delta_minor = CAST(ROUND(closing_delta * 100) AS INTEGER);
+ALTER TABLE card_statements DROP COLUMN closing_delta;
+ALTER TABLE card_statements RENAME COLUMN closing_delta_minor TO closing_delta;
+
+ALTER TABLE card_statements ADD COLUMN amount_paid_minor INTEGER NOT NULL DEFAULT 0;
+UPDATE card_statements SET amount_paid_minor = CAST(ROUND(amount_paid * 100) AS INTEGER);
+ALTER TABLE card_statements DROP COLUMN amount_paid;
+ALTER TABLE card_statements RENAME COLUMN amount_paid_minor TO amount_paid;
+
+ALTER TABLE card_statement_events ADD COLUMN total_due_input_minor INTEGER NOT NULL DEFAULT 0;
+UPDATE card_statement_events SET total_due_input_minor = CAST(ROUND(total_due_input * 100) AS INTEGER);
+ALTER TABLE card_statement_events DROP COLUMN total_due_input;
+ALTER TABLE card_statement_events RENAME COLUMN total_due_input_minor TO total_due_input;
@@ -0,0 +1,9 @@
+-- Gives every card event a type. total_due_input stays the total due after the
+-- event; amount is what was entered for anything other than a due snapshot.
+ALTER TABLE card_events ADD COLUMN event_type TEXT NOT NULL DEFAULT 'due'
+    CHECK (event_type IN ('due', 'payment', 'refund', 'fee', 'adjustment'));
+ALTER TABLE card_events ADD COLUMN amount INTEGER;
+
+-- Existing events were all snapshots, so the defaults describe them.
+ALTER TABLE card_statement_events ADD COLUMN event_type TEXT NOT NULL DEFAULT 'due';
+ALTER TABLE card_statement_events ADD COLUMN amount INTEGER;
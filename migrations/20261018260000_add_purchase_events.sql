@@ -0,0 +1,33 @@
+-- Itemized purchases: a purchase event adds its amount to the total due and
+-- records where the money went. The event_type CHECK can't be altered in place,
+-- so card_events is rebuilt (keeping rowids, which order same-second events).
+CREATE TABLE card_events_new (
+    transaction_id TEXT PRIMARY KEY,
+    card_id TEXT NOT NULL,
+    timestamp DATETIME DEFAULT CURRENT_TIMESTAMP,
+    total_due_input INTEGER NOT NULL DEFAULT 0,
+    event_type TEXT NOT NULL DEFAULT 'due'
+        CHECK (event_type IN ('due', 'payment', 'refund', 'fee', 'adjustment', 'purchase')),
+    amount INTEGER,
+    merchant TEXT,
+    category TEXT,
+
+    FOREIGN KEY (card_id)
+        REFERENCES cards (card_id)
+        ON DELETE CASCADE
+);
+
+INSERT INTO card_events_new (rowid, transaction_id, card_id, timestamp, total_due_input, event_type, amount)
+SELECT rowid, transaction_id, card_id, timestamp, total_due_input, event_type, amount
+FROM card_events;
+
+DROP TABLE card_events;
+ALTER TABLE card_events_new RENAME TO card_events;
+
+CREATE INDEX idx_card_events_card_id
+    ON card_events (card_id);
+CREATE INDEX idx_card_events_card_id_timestamp
+    ON card_events (card_id, timestamp DESC);
+
+ALTER TABLE card_statement_events ADD COLUMN merchant TEXT;
+ALTER TABLE card_statement_events ADD COLUMN category TEXT;
@@ -43,6 +43,45 @@ pub fn build_router(state: AppState) -> Router {
                     .on_response(DefaultOnResponse::new().level(Level::INFO)),
             ),
         )
+        .nest(
+            "/fx",
+            routes::fx::routes(state.clone())
+                .layer(
+                    TraceLayer::new_for_http()
+                        .make_span_with(DefaultMakeSpan::new().level(Level::INFO))
+                        .on_response(DefaultOnResponse::new().level(Level::INFO)),
+                )
+                .layer(middleware::from_fn_with_state(
+                    state.clone(),
+                    token_validator_middleware,
+                )),
+        )
+        .nest(
+            "/issuers",
+            routes::issuer::routes(state.clone())
+                .layer(
+                    TraceLayer::new_for_http()
+                        .make_span_with(DefaultMakeSpan::new().level(Level::INFO))
+                        .on_response(DefaultOnResponse::new().level(Level::INFO)),
+                )
+                .layer(middleware::from_fn_with_state(
+                    state.clone(),
+                    token_validator_middleware,
+                )),
+        )
+        .nest(
+            "/admin",
+            routes::admin::routes(state.clone())
+                .layer(
+                    TraceLayer::new_for_http()
+                        .make_span_with(DefaultMakeSpan::new().level(Level::INFO))
+                        .on_response(DefaultOnResponse::new().level(Level::INFO)),
+                )
+                .layer(middleware::from_fn_with_state(
+                    state.clone(),
+                    token_validator_middleware,
+                )),
+        )
         .nest(
             "/card",
             routes::card::routes(state.clone())
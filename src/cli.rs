@@ -0,0 +1,121 @@
+use sqlx::SqlitePool;
+
+use crate::handlers::{card_event, currency};
+
+const USAGE: &str = "usage:
+  flinderax fx list
+  flinderax fx set <BASE> <QUOTE> <RATE> [YYYY-MM-DD]
+  flinderax fx delete <BASE> <QUOTE>
+  flinderax state rebuild [CARD_ID] [--apply]";
+
+/// Runs a maintenance command against the database instead of starting the server.
+pub async fn run(db: &SqlitePool, args: &[String]) -> Result<(), sqlx::Error> {
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    match args.as_slice() {
+        ["fx", "list"] => {
+            for rate in currency::list_rates(db).await? {
+                println!(
+                    "{} {} {} (as of {})",
+                    rate.base_currency, rate.quote_currency, rate.rate, rate.as_of
+                );
+            }
+        }
+        ["fx", "set", base, quote, rate, rest @ ..] if rest.len() <= 1 => {
+            let (base, quote) = currency_pair(base, quote);
+            let rate = match rate.parse::<f64>() {
+                Ok(rate) if rate.is_finite() && rate > 0.0 => rate,
+                _ => usage_error("RATE must be a positive number"),
+            };
+            let as_of = currency::parse_as_of(rest.first().copied())
+                .unwrap_or_else(|| usage_error("as-of date must be YYYY-MM-DD"));
+            currency::upsert_rate(db, &base, &quote, rate, &as_of).await?;
+            println!("{} {} {} (as of {})", base, quote, rate, as_of);
+        }
+        ["fx", "delete", base, quote] => {
+            let (base, quote) = currency_pair(base, quote);
+            if currency::delete_rate(db, &base, &quote).await? {
+                println!("Deleted {} {}", base, quote);
+            } else {
+                usage_error(&format!("No rate stored for {} {}", base, quote));
+            }
+        }
+        ["state", "rebuild", rest @ ..] => {
+            let apply = rest.contains(&"--apply");
+            let card_ids: Vec<&str> = rest
+                .iter()
+                .copied()
+                .filter(|arg| *arg != "--apply")
+                .collect();
+            if card_ids.len() > 1 || card_ids.iter().any(|arg| arg.starts_with("--")) {
+                usage_error("state rebuild takes at most one CARD_ID and --apply");
+            }
+            rebuild_state(db, card_ids.first().copied(), apply).await?;
+        }
+        _ => usage_error("unknown command"),
+    }
+    Ok(())
+}
+
+/// Replays card events and reports running-state drift; writes fixes only
+/// with `--apply`.
+async fn rebuild_state(
+    db: &SqlitePool,
+    card_id: Option<&str>,
+    apply: bool,
+) -> Result<(), sqlx::Error> {
+    let (checked, mismatches) = card_event::rebuild_running_states(db, card_id, !apply).await?;
+    if card_id.is_some() && checked == 0 {
+        usage_error("No card with that id");
+    }
+    for mismatch in &mismatches {
+        let stored = match (mismatch.stored_total_due, mismatch.stored_delta) {
+            (Some(total), Some(delta)) => format!("{} (delta {})", total, delta),
+            _ => "missing".to_string(),
+        };
+        println!(
+            "{}: stored {}, replayed {} (delta {}), {} stale event totals{}",
+            mismatch.card_id,
+            stored,
+            mismatch.replayed_total_due,
+            mismatch.replayed_delta,
+            mismatch.stale_events,
+            if mismatch.repaired { ", repaired" } else { "" }
+        );
+    }
+    println!(
+        "Checked {} cards, {} mismatched{}",
+        checked,
+        mismatches.len(),
+        if apply {
+            ""
+        } else {
+            " (dry run; pass --apply to repair)"
+        }
+    );
+    Ok(())
+}
+
+fn currency_pair(base: &str, quote: &str) -> (String, String) {
+    let parse = |code: &str| {
+        let parsed = currency::parse_currency_code(code).unwrap_or_else(|| {
+            usage_error(&format!("{} is not a three-letter currency code", code))
+        });
+        if !currency::has_cents(&parsed) {
+            usage_error(&format!(
+                "{} is not supported; only currencies with two decimal places are",
+                parsed
+            ));
+        }
+        parsed
+    };
+    let (base, quote) = (parse(base), parse(quote));
+    if base == quote {
+        usage_error("BASE and QUOTE must differ");
+    }
+    (base, quote)
+}
+
+fn usage_error(message: &str) -> ! {
+    eprintln!("{}\n{}", message, USAGE);
+    std::process::exit(2)
+}
@@ -0,0 +1,151 @@
+use axum::{
+    extract::{FromRequest, Request},
+    http::StatusCode,
+    Json,
+};
+use serde::de::DeserializeOwned;
+use tracing::error;
+
+use crate::{
+    handlers::common::AppError,
+    models::{
+        AppState, ArchiveCardPayload, CardStatus, CloseStatementPayload, DeleteCardPayload,
+        DeleteTransactionPayload, GetCardForUser, GetCardRevisionsPayload, GetHistoryPayload,
+        GetStatementPayload, GetStatementsPayload, InsertTransactionPayload, PinCardPayload,
+        PurgeCardPayload, ReplaceCardPayload, ResetTransactionsPayload, RestoreCardPayload,
+        SetCardStatusPayload, SetCardTagsPayload, SetUtilizationThresholdsPayload,
+        UndoTransactionPayload, UpdateCardPayload, UpdateStatementPaymentPayload,
+        UpdateTransactionPayload,
+    },
+};
+
+/// Request payloads that address a single card through a `card_id` field.
+pub trait CardScoped {
+    fn card_id(&self) -> &str;
+}
+
+/// JSON body extractor for card routes.
+///
+/// Rejects the request with 404 before the handler runs unless the card named
+/// in the payload belongs to the authenticated user, so cross-user access looks
+/// the same as a card that does not exist. Soft-deleted cards are treated as
+/// missing; use [`TrashedCard`] for routes that operate on the trash.
+pub struct OwnedCard<T> {
+    pub card_id: String,
+    pub user_id: String,
+    pub archived: bool,
+    pub status: CardStatus,
+    pub payload: T,
+}
+
+/// Like [`OwnedCard`], but only resolves cards that are currently in the trash.
+pub struct TrashedCard<T>(pub OwnedCard<T>);
+
+impl<T> FromRequest<AppState> for OwnedCard<T>
+where
+    T: DeserializeOwned + CardScoped + Send,
+{
+    type Rejection = AppError;
+
+    async fn from_request(req: Request, state: &AppState) -> Result<Self, Self::Rejection> {
+        authorize_card(req, state, false).await
+    }
+}
+
+impl<T> FromRequest<AppState> for TrashedCard<T>
+where
+    T: DeserializeOwned + CardScoped + Send,
+{
+    type Rejection = AppError;
+
+    async fn from_request(req: Request, state: &AppState) -> Result<Self, Self::Rejection> {
+        authorize_card(req, state, true).await.map(TrashedCard)
+    }
+}
+
+async fn authorize_card<T>(
+    req: Request,
+    state: &AppState,
+    in_trash: bool,
+) -> Result<OwnedCard<T>, AppError>
+where
+    T: DeserializeOwned + CardScoped + Send,
+{
+    let (user_id, _role) = req
+        .extensions()
+        .get::<(String, String)>()
+        .cloned()
+        .ok_or_else(|| AppError(StatusCode::UNAUTHORIZED, "error".to_string()))?;
+
+    let Json(payload) = Json::<T>::from_request(req, state)
+        .await
+        .map_err(|e| AppError(e.status(), e.body_text()))?;
+    let card_id = payload.card_id().to_string();
+
+    let card = sqlx::query!(
+        r#"SELECT archived_at IS NOT NULL as "archived!: bool", status
+         FROM cards
+         WHERE card_id = ? AND user_id = ? AND (deleted_at IS NOT NULL) = ?"#,
+        card_id,
+        user_id,
+        in_trash
+    )
+    .fetch_optional(&state.db)
+    .await
+    .map_err(|e| {
+        error!("Error checking card ownership {}", e);
+        AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+    })?;
+
+    let card = card.ok_or_else(|| {
+        AppError(
+            StatusCode::NOT_FOUND,
+            "Card not found or you don't have permission to access it".to_string(),
+        )
+    })?;
+
+    Ok(OwnedCard {
+        card_id,
+        user_id,
+        archived: card.archived,
+        status: CardStatus::parse(&card.status).unwrap_or(CardStatus::Active),
+        payload,
+    })
+}
+
+macro_rules! card_scoped {
+    ($($payload:ty),* $(,)?) => {
+        $(
+            impl CardScoped for $payload {
+                fn card_id(&self) -> &str {
+                    &self.card_id
+                }
+            }
+        )*
+    };
+}
+
+card_scoped!(
+    ArchiveCardPayload,
+    CloseStatementPayload,
+    DeleteCardPayload,
+    DeleteTransactionPayload,
+    GetCardForUser,
+    GetCardRevisionsPayload,
+    GetHistoryPayload,
+    GetStatementPayload,
+    GetStatementsPayload,
+    InsertTransactionPayload,
+    PinCardPayload,
+    PurgeCardPayload,
+    ReplaceCardPayload,
+    ResetTransactionsPayload,
+    RestoreCardPayload,
+    SetCardStatusPayload,
+    SetCardTagsPayload,
+    SetUtilizationThresholdsPayload,
+    UndoTransactionPayload,
+    UpdateCardPayload,
+    UpdateStatementPaymentPayload,
+    UpdateTransactionPayload,
+);
@@ -0,0 +1,228 @@
+use time::{util::days_in_month, Date, Duration, Month};
+
+/// When a statement's payment falls due.
+#[derive(Clone, Copy)]
+pub enum DueRule {
+    /// A fixed day of the month, the first one strictly after the statement date.
+    DayOfMonth(u8),
+    /// A number of days after the statement date.
+    OffsetDays(i64),
+}
+
+pub struct BillingDates {
+    pub next_statement_date: Date,
+    pub next_due_date: Option<Date>,
+    pub days_until_due: Option<i64>,
+}
+
+pub fn due_rule(
+    payment_due_day: Option<i64>,
+    payment_due_offset_days: Option<i64>,
+) -> Option<DueRule> {
+    match (payment_due_day, payment_due_offset_days) {
+        (Some(day), _) => Some(DueRule::DayOfMonth(day as u8)),
+        (None, Some(offset)) => Some(DueRule::OffsetDays(offset)),
+        (None, None) => None,
+    }
+}
+
+/// The given day in a month, pulled back to the month's last day when the
+/// month is shorter (a statement day of 31 lands on Feb 28/29, Apr 30, ...).
+pub fn clamped_date(year: i32, month: Month, day: u8) -> Date {
+    let day = day.min(days_in_month(month, year));
+    Date::from_calendar_date(year, month, day).expect("day clamped to month length")
+}
+
+/// The statement date on or after `today`.
+pub fn next_statement_date(today: Date, statement_day: u8) -> Date {
+    let this_month = clamped_date(today.year(), today.month(), statement_day);
+    if this_month >= today {
+        this_month
+    } else {
+        let (year, month) = next_month(today.year(), today.month());
+        clamped_date(year, month, statement_day)
+    }
+}
+
+/// The statement date on or before `today`.
+pub fn previous_statement_date(today: Date, statement_day: u8) -> Date {
+    let this_month = clamped_date(today.year(), today.month(), statement_day);
+    if this_month <= today {
+        this_month
+    } else {
+        let (year, month) = previous_month(today.year(), today.month());
+        clamped_date(year, month, statement_day)
+    }
+}
+
+pub fn due_date(statement_date: Date, rule: DueRule) -> Date {
+    match rule {
+        DueRule::OffsetDays(days) => statement_date + Duration::days(days),
+        DueRule::DayOfMonth(day) => {
+            let this_month = clamped_date(statement_date.year(), statement_date.month(), day);
+            if this_month > statement_date {
+                this_month
+            } else {
+                let (year, month) = next_month(statement_date.year(), statement_date.month());
+                clamped_date(year, month, day)
+            }
+        }
+    }
+}
+
+/// Upcoming statement and payment dates for a card as seen on `today`.
+///
+/// The next due date belongs to the last closed statement while it is still
+/// ahead, and to the upcoming statement once it has passed.
+pub fn billing_dates(today: Date, statement_day: u8, rule: Option<DueRule>) -> BillingDates {
+    let next_statement = next_statement_date(today, statement_day);
+    let next_due = rule.map(|rule| {
+        let previous_due = due_date(previous_statement_date(today, statement_day), rule);
+        if previous_due >= today {
+            previous_due
+        } else {
+            due_date(next_statement, rule)
+        }
+    });
+
+    BillingDates {
+        next_statement_date: next_statement,
+        next_due_date: next_due,
+        days_until_due: next_due.map(|due| (due - today).whole_days()),
+    }
+}
+
+fn next_month(year: i32, month: Month) -> (i32, Month) {
+    match month {
+        Month::December => (year + 1, Month::January),
+        _ => (year, month.next()),
+    }
+}
+
+fn previous_month(year: i32, month: Month) -> (i32, Month) {
+    match month {
+        Month::January => (year - 1, Month::December),
+        _ => (year, month.previous()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use time::macros::date;
+
+    use super::*;
+
+    #[test]
+    fn day_31_lands_on_the_last_day_of_short_months() {
+        assert_eq!(
+            clamped_date(2026, Month::February, 31),
+            date!(2026 - 02 - 28)
+        );
+        assert_eq!(clamped_date(2026, Month::April, 31), date!(2026 - 04 - 30));
+        assert_eq!(clamped_date(2026, Month::March, 31), date!(2026 - 03 - 31));
+
+        assert_eq!(
+            next_statement_date(date!(2026 - 02 - 10), 31),
+            date!(2026 - 02 - 28)
+        );
+        assert_eq!(
+            next_statement_date(date!(2026 - 02 - 28), 31),
+            date!(2026 - 02 - 28)
+        );
+        assert_eq!(
+            next_statement_date(date!(2026 - 03 - 01), 31),
+            date!(2026 - 03 - 31)
+        );
+        assert_eq!(
+            previous_statement_date(date!(2026 - 03 - 15), 31),
+            date!(2026 - 02 - 28)
+        );
+    }
+
+    #[test]
+    fn february_29_only_exists_in_leap_years() {
+        assert_eq!(
+            clamped_date(2028, Month::February, 31),
+            date!(2028 - 02 - 29)
+        );
+        assert_eq!(
+            next_statement_date(date!(2028 - 02 - 15), 29),
+            date!(2028 - 02 - 29)
+        );
+        assert_eq!(
+            next_statement_date(date!(2027 - 02 - 15), 29),
+            date!(2027 - 02 - 28)
+        );
+        assert_eq!(
+            next_statement_date(date!(2028 - 02 - 15), 30),
+            date!(2028 - 02 - 29)
+        );
+        assert_eq!(
+            due_date(date!(2028 - 01 - 31), DueRule::DayOfMonth(30)),
+            date!(2028 - 02 - 29)
+        );
+        assert_eq!(
+            due_date(date!(2027 - 01 - 31), DueRule::DayOfMonth(30)),
+            date!(2027 - 02 - 28)
+        );
+    }
+
+    #[test]
+    fn december_rolls_over_into_january() {
+        assert_eq!(
+            next_statement_date(date!(2026 - 12 - 20), 15),
+            date!(2027 - 01 - 15)
+        );
+        assert_eq!(
+            previous_statement_date(date!(2027 - 01 - 10), 15),
+            date!(2026 - 12 - 15)
+        );
+        assert_eq!(
+            due_date(date!(2026 - 12 - 15), DueRule::DayOfMonth(5)),
+            date!(2027 - 01 - 05)
+        );
+        assert_eq!(
+            due_date(date!(2026 - 12 - 20), DueRule::OffsetDays(20)),
+            date!(2027 - 01 - 09)
+        );
+    }
+
+    #[test]
+    fn due_day_on_or_before_the_statement_day_falls_in_the_next_month() {
+        assert_eq!(
+            due_date(date!(2026 - 03 - 15), DueRule::DayOfMonth(15)),
+            date!(2026 - 04 - 15)
+        );
+        assert_eq!(
+            due_date(date!(2026 - 03 - 15), DueRule::DayOfMonth(10)),
+            date!(2026 - 04 - 10)
+        );
+        assert_eq!(
+            due_date(date!(2026 - 03 - 05), DueRule::DayOfMonth(20)),
+            date!(2026 - 03 - 20)
+        );
+    }
+
+    #[test]
+    fn next_due_date_stays_on_the_last_statement_until_it_passes() {
+        let rule = due_rule(Some(25), None);
+        let dates = billing_dates(date!(2026 - 03 - 10), 5, rule);
+        assert_eq!(dates.next_statement_date, date!(2026 - 04 - 05));
+        assert_eq!(dates.next_due_date, Some(date!(2026 - 03 - 25)));
+        assert_eq!(dates.days_until_due, Some(15));
+
+        let dates = billing_dates(date!(2026 - 03 - 28), 5, rule);
+        assert_eq!(dates.next_due_date, Some(date!(2026 - 04 - 25)));
+        assert_eq!(dates.days_until_due, Some(28));
+
+        // Due day before the statement day, across the year end.
+        let dates = billing_dates(date!(2026 - 12 - 25), 20, due_rule(Some(10), None));
+        assert_eq!(dates.next_statement_date, date!(2027 - 01 - 20));
+        assert_eq!(dates.next_due_date, Some(date!(2027 - 01 - 10)));
+        assert_eq!(dates.days_until_due, Some(16));
+
+        let dates = billing_dates(date!(2026 - 03 - 10), 5, None);
+        assert_eq!(dates.next_due_date, None);
+        assert_eq!(dates.days_until_due, None);
+    }
+}
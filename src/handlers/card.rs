@@ -1,6 +1,6 @@
 use axum::{
-    extract::State,
-    http::{header, StatusCode},
+    extract::{Query, State},
+    http::{header, HeaderMap, StatusCode},
     response::IntoResponse,
     Extension, Json,
 };
@@ -8,18 +8,30 @@ use time::{format_description::well_known::Rfc3339, OffsetDateTime, PrimitiveDat
 use nanoid::nanoid;
 use prost::Message;
 use redis::AsyncCommands;
+use sqlx::{SqliteConnection, SqlitePool};
 use tracing::error;
 
 use crate::{
+    extractors::{OwnedCard, TrashedCard},
     handlers::{
+        billing::{self, BillingDates},
+        card_event, card_list,
         color::{self, pack, unpack},
         common::AppError,
+        currency::currency_code_or_400,
+        idempotency::{self, Reservation, Reserved},
+        issuer,
+        theme::Theme,
     },
     models::{
-        AppState, CardResponse, CreateCardPayload, DeleteCardPayload, GetCardForUser,
-        InsertTransactionPayload, InsertTransactionResponse, ResetTransactionsPayload,
-        ShowGetCardResponse, UpdateCardPayload,
+        AppState, ArchiveCardPayload, CardResponse, CardRevision, CardStatus, CreateCardPayload,
+        DeleteCardPayload, EventType, GetAllCardsQuery, GetCardForUser, GetCardRevisionsPayload,
+        InsertTransactionPayload, InsertTransactionResponse, PinCardPayload, PurgeCardPayload,
+        ReorderCardsPayload, ReorderCardsResponse, ResetTransactionsPayload, RestoreCardPayload,
+        SetUtilizationThresholdsPayload, ShowGetCardResponse, Tag, TrashedCardResponse,
+        UpdateCardPayload, UtilizationThresholdsResponse, UtilizationWarning,
     },
+    money::Money,
 };
 struct Timestamp {
     seconds: i64,
@@ -54,7 +66,13 @@ impl From<crate::models::CardTransactionHistory> for crate::proto::CardTransacti
         let timestamp = parse_timestamp(&value.timestamp);
         crate::proto::CardTransactionHistory {
             transaction_id: value.transaction_id,
-            total_due_input: value.total_due_input,
+            card_id: value.card_id,
+            total_due_input: value.total_due_input.to_major() as f32,
+            total_due_input_minor: value.total_due_input.minor(),
+            event_type: value.event_type.as_str().to_string(),
+            amount_minor: value.amount.map(Money::minor),
+            merchant: value.merchant,
+            category: value.category,
             timestamp_seconds: timestamp.seconds,
             timestamp_nanos: timestamp.nanos,
         }
@@ -67,14 +85,207 @@ impl From<crate::models::ShowGetCardResponse> for crate::proto::Card {
             card_id: param.card_id,
             card_name: param.card_name,
             card_bank: param.card_bank,
-            card_primary_color: color::pack(param.card_primary_color),
-            card_secondary_color: color::pack(param.card_secondary_color),
-            last_total_due: param.last_total_due,
-            last_delta: param.last_delta,
+            card_primary_color: color::pack(param.card_primary_color) as i32,
+            card_secondary_color: color::pack(param.card_secondary_color) as i32,
+            last_total_due: param.last_total_due.map(|v| v.to_major() as f32),
+            last_delta: param.last_delta.map(|v| v.to_major() as f32),
+            archived: param.archived,
+            credit_limit: param.credit_limit.map(|v| v.to_major() as f32),
+            utilization_percent: param.utilization_percent,
+            next_statement_date: param.next_statement_date,
+            next_due_date: param.next_due_date,
+            days_until_due: param.days_until_due.map(|v| v as i32),
+            position: param.position as i32,
+            pinned: param.pinned,
+            network: param.network,
+            last_four: param.last_four,
+            expiry_month: param.expiry_month.map(i32::from),
+            expiry_year: param.expiry_year.map(i32::from),
+            expiring: param.expiring,
+            expired: param.expired,
+            currency: param.currency,
+            tags: param
+                .tags
+                .into_iter()
+                .map(|tag| crate::proto::CardTag {
+                    tag_id: tag.tag_id,
+                    name: tag.name,
+                })
+                .collect(),
+            updated_at: param.updated_at,
+            issuer_id: param.issuer_id,
+            issuer_logo_path: param.issuer_logo_path,
+            theme: Some(param.theme.into()),
+            status: param.status.as_str().to_string(),
+            status_changed_at: param.status_changed_at,
+            replaced_by: param.replaced_by,
+            replaces: param.replaces,
+            last_total_due_minor: param.last_total_due.map(Money::minor),
+            last_delta_minor: param.last_delta.map(Money::minor),
+            credit_limit_minor: param.credit_limit.map(Money::minor),
+            historical: param.historical,
+            as_of: param.as_of,
         }
     }
 }
 
+/// Share of the credit limit taken up by the current total due, in percent.
+fn utilization_percent(total_due: Money, credit_limit: Option<Money>) -> Option<f32> {
+    credit_limit
+        .filter(|limit| limit.is_positive())
+        .map(|limit| (total_due.minor() as f64 / limit.minor() as f64 * 100.0) as f32)
+}
+
+fn card_billing_dates(
+    statement_day: Option<i64>,
+    payment_due_day: Option<i64>,
+    payment_due_offset_days: Option<i64>,
+) -> Option<BillingDates> {
+    let today = OffsetDateTime::now_utc().date();
+    statement_day.map(|day| {
+        billing::billing_dates(
+            today,
+            day as u8,
+            billing::due_rule(payment_due_day, payment_due_offset_days),
+        )
+    })
+}
+
+fn validate_billing_cycle(
+    statement_day: Option<u8>,
+    payment_due_day: Option<u8>,
+    payment_due_offset_days: Option<u8>,
+) -> Result<(), AppError> {
+    let bad_request = |message: &str| Err(AppError(StatusCode::BAD_REQUEST, message.to_string()));
+    if statement_day.is_some_and(|day| !(1..=31).contains(&day)) {
+        return bad_request("statement_day must be between 1 and 31");
+    }
+    if payment_due_day.is_some_and(|day| !(1..=31).contains(&day)) {
+        return bad_request("payment_due_day must be between 1 and 31");
+    }
+    if payment_due_offset_days.is_some_and(|days| !(1..=60).contains(&days)) {
+        return bad_request("payment_due_offset_days must be between 1 and 60");
+    }
+    if payment_due_day.is_some() && payment_due_offset_days.is_some() {
+        return bad_request("Set either payment_due_day or payment_due_offset_days, not both");
+    }
+    Ok(())
+}
+
+/// Seconds until the cached card list should expire: at most an hour, and
+/// never past UTC midnight so day counts like `days_until_due` stay current.
+fn card_cache_ttl() -> u64 {
+    let now = OffsetDateTime::now_utc();
+    let midnight = now.date().next_day().map(|day| day.midnight().assume_utc());
+    midnight
+        .map(|midnight| (midnight - now).whole_seconds().clamp(1, 3600) as u64)
+        .unwrap_or(3600)
+}
+
+fn validate_credit_limit(credit_limit: Option<Money>) -> Result<(), AppError> {
+    match credit_limit {
+        Some(limit) if !limit.is_positive() => Err(AppError(
+            StatusCode::BAD_REQUEST,
+            "credit_limit must be a positive amount".to_string(),
+        )),
+        _ => Ok(()),
+    }
+}
+
+fn validate_issuer_details(
+    last_four: Option<&str>,
+    expiry_month: Option<u8>,
+    expiry_year: Option<u16>,
+) -> Result<(), AppError> {
+    let bad_request = |message: &str| Err(AppError(StatusCode::BAD_REQUEST, message.to_string()));
+    if last_four
+        .is_some_and(|digits| digits.len() != 4 || !digits.bytes().all(|b| b.is_ascii_digit()))
+    {
+        return bad_request(
+            "last_four must be exactly four digits; never send the full card number",
+        );
+    }
+    if expiry_month.is_some_and(|month| !(1..=12).contains(&month)) {
+        return bad_request("expiry_month must be between 1 and 12");
+    }
+    if expiry_year.is_some_and(|year| !(2000..=2099).contains(&year)) {
+        return bad_request("expiry_year must be a four-digit year");
+    }
+    if expiry_month.is_some() != expiry_year.is_some() {
+        return bad_request("Set expiry_month and expiry_year together");
+    }
+    Ok(())
+}
+
+/// Refuses free-text fields that look like they hold a full card number, i.e.
+/// a run of 12 or more digits once spaces and dashes are ignored.
+fn reject_card_number(field: &str, value: &str) -> Result<(), AppError> {
+    let mut run = 0;
+    for c in value.chars() {
+        match c {
+            '0'..='9' => run += 1,
+            ' ' | '-' => {}
+            _ => run = 0,
+        }
+        if run >= 12 {
+            return Err(AppError(
+                StatusCode::BAD_REQUEST,
+                format!("{} must not contain a card number", field),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// How many days before the end of its expiry month a card counts as expiring.
+const EXPIRING_WINDOW_DAYS: i64 = 60;
+
+/// `(expiring, expired)` for a card that is valid through the last day of its
+/// expiry month.
+fn expiry_flags(expiry_month: Option<i64>, expiry_year: Option<i64>) -> (bool, bool) {
+    let (Some(month), Some(year)) = (expiry_month, expiry_year) else {
+        return (false, false);
+    };
+    let Ok(month) = time::Month::try_from(month as u8) else {
+        return (false, false);
+    };
+    let last_valid_day = billing::clamped_date(year as i32, month, 31);
+    let days_left = (last_valid_day - OffsetDateTime::now_utc().date()).whole_days();
+    (
+        (0..=EXPIRING_WINDOW_DAYS).contains(&days_left),
+        days_left < 0,
+    )
+}
+
+/// Decodes the JSON array of `{tag_id, name}` objects that card queries select
+/// with `json_group_array`.
+fn parse_card_tags(tags: &str) -> Result<Vec<Tag>, AppError> {
+    serde_json::from_str(tags).map_err(|e| {
+        error!("Error decoding card tags {}", e);
+        AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+    })
+}
+
+/// Bumped on every change to the user's cards. Cached lists embed the
+/// generation in their key, so a bump orphans all of them at once and they
+/// age out through their TTL instead of having to be enumerated.
+fn card_cache_generation_key(user_id: &str) -> String {
+    format!("user_cards_gen:{}", user_id)
+}
+
+fn card_cache_key(user_id: &str, generation: i64, variant: &str) -> String {
+    format!("user_cards_proto_v3:{}:{}:{}", user_id, generation, variant)
+}
+
+pub async fn invalidate_card_cache(state: &AppState, user_id: &str) {
+    if let Some(mut redis) = state.redis.clone() {
+        let _: i64 = redis
+            .incr(card_cache_generation_key(user_id), 1)
+            .await
+            .unwrap_or_default();
+    }
+}
+
 pub async fn create_card(
     State(state): State<AppState>,
     Extension((user_id, _role)): Extension<(String, String)>,
@@ -85,18 +296,94 @@ pub async fn create_card(
         AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
     })?;
 
+    let card_id = insert_card(&mut tx, &user_id, card_details).await?;
+
+    tx.commit()
+        .await
+        .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    invalidate_card_cache(&state, &user_id).await;
+
+    Ok(Json(CardResponse {
+        card_id,
+        status: true,
+    }))
+}
+
+/// Validates and inserts a card along with its running state. The caller owns
+/// the transaction and the cache invalidation.
+pub async fn insert_card(
+    tx: &mut SqliteConnection,
+    user_id: &str,
+    card_details: CreateCardPayload,
+) -> Result<String, AppError> {
+    validate_credit_limit(card_details.credit_limit)?;
+    validate_billing_cycle(
+        card_details.statement_day,
+        card_details.payment_due_day,
+        card_details.payment_due_offset_days,
+    )?;
+    validate_issuer_details(
+        card_details.last_four.as_deref(),
+        card_details.expiry_month,
+        card_details.expiry_year,
+    )?;
+    reject_card_number("card_name", &card_details.card_name)?;
+    reject_card_number("card_bank", &card_details.card_bank)?;
+    let currency = card_details
+        .currency
+        .as_deref()
+        .map(|code| currency_code_or_400("currency", code))
+        .transpose()?;
+
+    let issuer = issuer::match_issuer(&mut *tx, &card_details.card_bank)
+        .await
+        .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let theme = Theme::resolve(
+        None,
+        card_details
+            .card_primary_color
+            .or(issuer.as_ref().map(|issuer| issuer.primary_color)),
+        card_details
+            .card_secondary_color
+            .or(issuer.as_ref().map(|issuer| issuer.secondary_color)),
+        card_details.theme.as_ref(),
+    )?;
+    let issuer_id = issuer.map(|issuer| issuer.issuer_id);
+
     let card_id = nanoid!();
-    let primary_color = color::pack(card_details.card_primary_color);
-    let secondary_color = color::pack(card_details.card_secondary_color);
+    let primary_color = color::pack(theme.primary_color.rgb);
+    let secondary_color = color::pack(theme.secondary_color.rgb);
+    let network = card_details.network.map(|network| network.as_str());
+    let theme_preset = theme.preset.map(|preset| preset.name);
 
     sqlx::query!(
-        "INSERT INTO cards (card_id, user_id, card_name, card_bank, card_primary_color, card_secondary_color) VALUES (?, ?, ?, ?, ?, ?)",
+        "INSERT INTO cards (card_id, user_id, card_name, card_bank, card_primary_color, card_secondary_color, card_primary_alpha, card_secondary_alpha, gradient_angle, theme_preset, credit_limit, statement_day, payment_due_day, payment_due_offset_days, network, last_four, expiry_month, expiry_year, issuer_id, currency, position)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?,
+                 COALESCE(?, (SELECT home_currency FROM users WHERE user_id = ?), 'INR'),
+                 (SELECT COALESCE(MAX(position) + 1, 0) FROM cards WHERE user_id = ?))",
         card_id,
         user_id,
         card_details.card_name,
         card_details.card_bank,
         primary_color,
-        secondary_color
+        secondary_color,
+        theme.primary_color.alpha,
+        theme.secondary_color.alpha,
+        theme.gradient_angle,
+        theme_preset,
+        card_details.credit_limit,
+        card_details.statement_day,
+        card_details.payment_due_day,
+        card_details.payment_due_offset_days,
+        network,
+        card_details.last_four,
+        card_details.expiry_month,
+        card_details.expiry_year,
+        issuer_id,
+        currency,
+        user_id,
+        user_id
     )
     .execute(&mut *tx)
     .await
@@ -105,137 +392,725 @@ pub async fn create_card(
     sqlx::query!(
         "INSERT INTO card_running_state (card_id, last_total_due, last_delta) VALUES (?, ?, ?)",
         card_id,
-        0.0,
-        0.0
+        0,
+        0
     )
     .execute(&mut *tx)
     .await
     .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
+    Ok(card_id)
+}
+
+pub async fn update(
+    State(state): State<AppState>,
+    OwnedCard {
+        card_id,
+        user_id,
+        payload: update_card_details,
+        ..
+    }: OwnedCard<UpdateCardPayload>,
+) -> Result<Json<ShowGetCardResponse>, AppError> {
+    let mut tx = state.db.begin().await.map_err(|e| {
+        error!("Error setting transaction check {} ", e);
+        AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+    })?;
+
+    let (card, changed) =
+        apply_card_update(&mut tx, &card_id, &user_id, update_card_details).await?;
+
     tx.commit()
         .await
         .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
-    // Invalidate cache
-    if let Some(mut redis) = state.redis.clone() {
-        let cache_key = format!("user_cards_proto_v2:{}", user_id);
-        let _: () = redis.del(cache_key).await.unwrap_or_default();
+    if changed {
+        invalidate_card_cache(&state, &user_id).await;
     }
 
-    Ok(Json(CardResponse {
-        card_id,
-        status: true,
-    }))
+    Ok(Json(card))
 }
 
-pub async fn update(
-    State(state): State<AppState>,
-    Extension((user_id, _role)): Extension<(String, String)>,
-    Json(update_card_details): Json<UpdateCardPayload>,
-) -> Result<Json<CardResponse>, AppError> {
-    let card_primary_color = pack(update_card_details.card_primary_color);
-    let card_secondary_color = pack(update_card_details.card_secondary_color);
-    sqlx::query!(
-        "UPDATE cards SET card_name = ?, card_bank = ?, card_primary_color = ?, card_secondary_color = ? WHERE card_id = ? AND user_id = ?",
-        update_card_details.card_name,
-        update_card_details.card_bank,
+/// A patched optional field: left out keeps `current`, `null` clears it.
+fn patch<T>(update: Option<Option<T>>, current: Option<T>) -> Option<T> {
+    match update {
+        Some(value) => value,
+        None => current,
+    }
+}
+
+/// Applies a partial update and records its revisions. Returns the updated card
+/// and whether anything changed; the caller owns the transaction and the cache.
+pub async fn apply_card_update(
+    tx: &mut SqliteConnection,
+    card_id: &str,
+    user_id: &str,
+    update_card_details: UpdateCardPayload,
+) -> Result<(ShowGetCardResponse, bool), AppError> {
+    let not_found = || {
+        AppError(
+            StatusCode::NOT_FOUND,
+            "Card not found or you don't have permission to update it".to_string(),
+        )
+    };
+
+    validate_credit_limit(update_card_details.credit_limit.flatten())?;
+    validate_billing_cycle(
+        update_card_details.statement_day.flatten(),
+        update_card_details.payment_due_day.flatten(),
+        update_card_details.payment_due_offset_days.flatten(),
+    )?;
+    if let Some(card_name) = &update_card_details.card_name {
+        reject_card_number("card_name", card_name)?;
+    }
+    if let Some(card_bank) = &update_card_details.card_bank {
+        reject_card_number("card_bank", card_bank)?;
+    }
+    let new_currency = update_card_details
+        .currency
+        .as_deref()
+        .map(|code| currency_code_or_400("currency", code))
+        .transpose()?;
+
+    let current = sqlx::query!(
+        r#"SELECT card_name, card_bank, card_primary_color, card_secondary_color, card_primary_alpha, card_secondary_alpha, gradient_angle, theme_preset, credit_limit as "credit_limit: Money", statement_day, payment_due_day, payment_due_offset_days, network, last_four, expiry_month, expiry_year, currency, issuer_id FROM cards WHERE card_id = ? AND user_id = ? AND deleted_at IS NULL"#,
+        card_id,
+        user_id
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .ok_or_else(not_found)?;
+
+    let current_theme = Theme::from_row(
+        current.card_primary_color,
+        current.card_primary_alpha,
+        current.card_secondary_color,
+        current.card_secondary_alpha,
+        current.gradient_angle,
+        current.theme_preset.as_deref(),
+    );
+
+    let card_name = update_card_details
+        .card_name
+        .unwrap_or_else(|| current.card_name.clone());
+    let card_bank = update_card_details
+        .card_bank
+        .unwrap_or_else(|| current.card_bank.clone());
+    let theme = Theme::resolve(
+        Some(current_theme),
+        update_card_details.card_primary_color,
+        update_card_details.card_secondary_color,
+        update_card_details.theme.as_ref(),
+    )?;
+    let credit_limit = patch(update_card_details.credit_limit, current.credit_limit);
+    let statement_day = patch(
+        update_card_details
+            .statement_day
+            .map(|day| day.map(i64::from)),
+        current.statement_day,
+    );
+    // The two due-date rules are exclusive, so setting one clears the other.
+    let (payment_due_day, payment_due_offset_days) = match (
+        update_card_details.payment_due_day,
+        update_card_details.payment_due_offset_days,
+    ) {
+        (Some(Some(day)), _) => (Some(i64::from(day)), None),
+        (_, Some(Some(days))) => (None, Some(i64::from(days))),
+        (day, days) => (
+            patch(day.map(|_| None), current.payment_due_day),
+            patch(days.map(|_| None), current.payment_due_offset_days),
+        ),
+    };
+    let network = patch(
+        update_card_details
+            .network
+            .map(|network| network.map(|network| network.as_str().to_string())),
+        current.network.clone(),
+    );
+    let last_four = patch(update_card_details.last_four, current.last_four.clone());
+    let expiry_month = patch(
+        update_card_details
+            .expiry_month
+            .map(|month| month.map(i64::from)),
+        current.expiry_month,
+    );
+    let expiry_year = patch(
+        update_card_details
+            .expiry_year
+            .map(|year| year.map(i64::from)),
+        current.expiry_year,
+    );
+    let currency = new_currency.unwrap_or_else(|| current.currency.clone());
+    // Follows the bank name; the card's colors are left as they are.
+    let issuer_id = if card_bank != current.card_bank {
+        issuer::match_issuer(&mut *tx, &card_bank)
+            .await
+            .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+            .map(|issuer| issuer.issuer_id)
+    } else {
+        current.issuer_id.clone()
+    };
+    validate_issuer_details(
+        last_four.as_deref(),
+        expiry_month.map(|month| month as u8),
+        expiry_year.map(|year| year as u16),
+    )?;
+
+    let mut changes: Vec<(&str, String, String)> = Vec::new();
+    if card_name != current.card_name {
+        changes.push(("card_name", current.card_name, card_name.clone()));
+    }
+    if card_bank != current.card_bank {
+        changes.push(("card_bank", current.card_bank, card_bank.clone()));
+    }
+    for (field_name, old_value, new_value) in [
+        (
+            "card_primary_color",
+            current_theme.primary_color,
+            theme.primary_color,
+        ),
+        (
+            "card_secondary_color",
+            current_theme.secondary_color,
+            theme.secondary_color,
+        ),
+    ] {
+        if old_value != new_value {
+            changes.push((field_name, old_value.to_hex(), new_value.to_hex()));
+        }
+    }
+    if theme.gradient_angle != current_theme.gradient_angle {
+        changes.push((
+            "gradient_angle",
+            current_theme.gradient_angle.to_string(),
+            theme.gradient_angle.to_string(),
+        ));
+    }
+    let theme_preset = theme.preset.map(|preset| preset.name);
+    if theme_preset != current_theme.preset.map(|preset| preset.name) {
+        changes.push((
+            "theme_preset",
+            current.theme_preset.clone().unwrap_or_default(),
+            theme_preset.unwrap_or_default().to_string(),
+        ));
+    }
+    for (field_name, old_value, new_value) in [
+        ("network", &current.network, &network),
+        ("last_four", &current.last_four, &last_four),
+    ] {
+        if old_value != new_value {
+            changes.push((
+                field_name,
+                old_value.clone().unwrap_or_default(),
+                new_value.clone().unwrap_or_default(),
+            ));
+        }
+    }
+    if currency != current.currency {
+        changes.push(("currency", current.currency, currency.clone()));
+    }
+    if credit_limit != current.credit_limit {
+        changes.push((
+            "credit_limit",
+            current
+                .credit_limit
+                .map(|v| v.to_string())
+                .unwrap_or_default(),
+            credit_limit.map(|v| v.to_string()).unwrap_or_default(),
+        ));
+    }
+    for (field_name, old_value, new_value) in [
+        ("statement_day", current.statement_day, statement_day),
+        ("payment_due_day", current.payment_due_day, payment_due_day),
+        (
+            "payment_due_offset_days",
+            current.payment_due_offset_days,
+            payment_due_offset_days,
+        ),
+        ("expiry_month", current.expiry_month, expiry_month),
+        ("expiry_year", current.expiry_year, expiry_year),
+    ] {
+        if old_value != new_value {
+            changes.push((
+                field_name,
+                old_value.map(|v| v.to_string()).unwrap_or_default(),
+                new_value.map(|v| v.to_string()).unwrap_or_default(),
+            ));
+        }
+    }
+
+    let card_primary_color = pack(theme.primary_color.rgb);
+    let card_secondary_color = pack(theme.secondary_color.rgb);
+    let result = sqlx::query!(
+        "UPDATE cards SET card_name = ?, card_bank = ?, card_primary_color = ?, card_secondary_color = ?, card_primary_alpha = ?, card_secondary_alpha = ?, gradient_angle = ?, theme_preset = ?, credit_limit = ?, statement_day = ?, payment_due_day = ?, payment_due_offset_days = ?, network = ?, last_four = ?, expiry_month = ?, expiry_year = ?, currency = ?, issuer_id = ? WHERE card_id = ? AND user_id = ?",
+        card_name,
+        card_bank,
         card_primary_color,
         card_secondary_color,
-        update_card_details.card_id,
+        theme.primary_color.alpha,
+        theme.secondary_color.alpha,
+        theme.gradient_angle,
+        theme_preset,
+        credit_limit,
+        statement_day,
+        payment_due_day,
+        payment_due_offset_days,
+        network,
+        last_four,
+        expiry_month,
+        expiry_year,
+        currency,
+        issuer_id,
+        card_id,
         user_id
     )
-    .execute(&state.db)
+    .execute(&mut *tx)
     .await
     .map_err(|e| {
         AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
     })?;
 
-    // Invalidate cache
-    if let Some(mut redis) = state.redis.clone() {
-        let cache_key = format!("user_cards_proto_v2:{}", user_id);
-        let _: () = redis.del(cache_key).await.unwrap_or_default();
+    if result.rows_affected() == 0 {
+        return Err(not_found());
     }
 
-    Ok(Json(CardResponse {
-        card_id: update_card_details.card_id,
-        status: true,
-    }))
+    let revision_id = nanoid!();
+    for (field_name, old_value, new_value) in &changes {
+        sqlx::query!(
+            "INSERT INTO card_revisions (revision_id, card_id, field_name, old_value, new_value) VALUES (?, ?, ?, ?, ?)",
+            revision_id,
+            card_id,
+            field_name,
+            old_value,
+            new_value
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| {
+            error!("Error recording card revision {} ", e);
+            AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+        })?;
+    }
+
+    let card = load_card(&mut *tx, card_id, user_id)
+        .await?
+        .ok_or_else(not_found)?;
+
+    Ok((card, !changes.is_empty()))
 }
 
-pub async fn get_card(
+pub async fn get_revisions(
     State(state): State<AppState>,
-    Extension((user_id, _role)): Extension<(String, String)>,
-    Json(get_card): Json<GetCardForUser>,
-) -> Result<Json<ShowGetCardResponse>, AppError> {
-    let card = sqlx::query!(
-        "SELECT c.card_id, c.card_name, c.card_bank, c.card_primary_color, c.card_secondary_color,
-                crs.last_total_due, crs.last_delta
+    OwnedCard { card_id, .. }: OwnedCard<GetCardRevisionsPayload>,
+) -> Result<Json<Vec<CardRevision>>, AppError> {
+    let revisions = sqlx::query_as!(
+        CardRevision,
+        r#"
+        SELECT revision_id as "revision_id!",
+        field_name as "field_name!",
+        old_value as "old_value!",
+        new_value as "new_value!",
+        changed_at as "changed_at!: String"
+        FROM card_revisions
+        WHERE card_id = ?
+        ORDER BY changed_at DESC, revision_id, field_name"#,
+        card_id
+    )
+    .fetch_all(&state.db)
+    .await
+    .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(revisions))
+}
+
+/// The columns every card response is built from. Read through
+/// `query_card_rows!` so single-card and list reads stay in step.
+struct CardRow {
+    card_id: String,
+    card_name: String,
+    card_bank: String,
+    card_primary_color: i64,
+    card_secondary_color: i64,
+    last_total_due: Option<Money>,
+    last_delta: Option<Money>,
+    archived: bool,
+    credit_limit: Option<Money>,
+    statement_day: Option<i64>,
+    payment_due_day: Option<i64>,
+    payment_due_offset_days: Option<i64>,
+    position: i64,
+    pinned: bool,
+    network: Option<String>,
+    last_four: Option<String>,
+    expiry_month: Option<i64>,
+    expiry_year: Option<i64>,
+    currency: String,
+    tags: String,
+    updated_at: Option<String>,
+    issuer_id: Option<String>,
+    issuer_logo_path: Option<String>,
+    card_primary_alpha: Option<i64>,
+    card_secondary_alpha: Option<i64>,
+    gradient_angle: i64,
+    theme_preset: Option<String>,
+    status: String,
+    status_changed_at: Option<String>,
+    replaced_by: Option<String>,
+    replaces: Option<String>,
+}
+
+/// Selects `CardRow`s from `cards c` joined with its running state and issuer,
+/// followed by `$filter` (the `WHERE` clause onwards) and its arguments.
+macro_rules! query_card_rows {
+    ($filter:literal $(, $args:expr)* $(,)?) => {
+        sqlx::query_as!(
+            CardRow,
+            r#"SELECT c.card_id as "card_id!", c.card_name, c.card_bank, c.card_primary_color, c.card_secondary_color,
+                crs.last_total_due as "last_total_due?: Money", crs.last_delta as "last_delta?: Money",
+                c.archived_at IS NOT NULL as "archived!: bool",
+                c.credit_limit as "credit_limit: Money", c.statement_day, c.payment_due_day, c.payment_due_offset_days,
+                c.position, c.pinned as "pinned: bool",
+                c.network, c.last_four, c.expiry_month, c.expiry_year, c.currency,
+                (SELECT json_group_array(json_object('tag_id', t.tag_id, 'name', t.name))
+                 FROM (SELECT t.tag_id, t.name FROM card_tags ct
+                       JOIN tags t ON t.tag_id = ct.tag_id
+                       WHERE ct.card_id = c.card_id
+                       ORDER BY t.name COLLATE NOCASE) t) as "tags!: String",
+                crs.updated_at as "updated_at?: String",
+                c.issuer_id, i.logo_path as "issuer_logo_path?: String",
+                c.card_primary_alpha, c.card_secondary_alpha, c.gradient_angle, c.theme_preset,
+                c.status, c.status_changed_at as "status_changed_at?: String", c.replaced_by,
+                (SELECT p.card_id FROM cards p WHERE p.replaced_by = c.card_id) as "replaces?: String"
          FROM cards c
          LEFT JOIN card_running_state crs ON c.card_id = crs.card_id
-         WHERE c.card_id = ? AND c.user_id = ?",
-        get_card.card_id,
+         LEFT JOIN issuers i ON i.issuer_id = c.issuer_id
+         "# + $filter
+            $(, $args)*
+        )
+    };
+}
+
+impl TryFrom<CardRow> for ShowGetCardResponse {
+    type Error = AppError;
+
+    fn try_from(card: CardRow) -> Result<Self, AppError> {
+        let tags = parse_card_tags(&card.tags)?;
+        let billing = card_billing_dates(
+            card.statement_day,
+            card.payment_due_day,
+            card.payment_due_offset_days,
+        );
+        let (expiring, expired) = expiry_flags(card.expiry_month, card.expiry_year);
+        let theme = Theme::from_row(
+            card.card_primary_color,
+            card.card_primary_alpha,
+            card.card_secondary_color,
+            card.card_secondary_alpha,
+            card.gradient_angle,
+            card.theme_preset.as_deref(),
+        );
+        Ok(ShowGetCardResponse {
+            card_id: card.card_id,
+            card_name: card.card_name,
+            card_bank: card.card_bank,
+            card_primary_color: unpack(card.card_primary_color),
+            card_secondary_color: unpack(card.card_secondary_color),
+            last_total_due: card.last_total_due,
+            last_delta: card.last_delta,
+            archived: card.archived,
+            credit_limit: card.credit_limit,
+            utilization_percent: card
+                .last_total_due
+                .and_then(|total| utilization_percent(total, card.credit_limit)),
+            statement_day: card.statement_day.map(|v| v as u8),
+            payment_due_day: card.payment_due_day.map(|v| v as u8),
+            payment_due_offset_days: card.payment_due_offset_days.map(|v| v as u8),
+            next_statement_date: billing.as_ref().map(|b| b.next_statement_date.to_string()),
+            next_due_date: billing
+                .as_ref()
+                .and_then(|b| b.next_due_date)
+                .map(|date| date.to_string()),
+            days_until_due: billing.as_ref().and_then(|b| b.days_until_due),
+            position: card.position,
+            pinned: card.pinned,
+            network: card.network,
+            last_four: card.last_four,
+            expiry_month: card.expiry_month.map(|v| v as u8),
+            expiry_year: card.expiry_year.map(|v| v as u16),
+            expiring,
+            expired,
+            currency: card.currency,
+            tags,
+            updated_at: card.updated_at,
+            issuer_id: card.issuer_id,
+            issuer_logo_path: card.issuer_logo_path,
+            theme: theme.card_theme(),
+            status: CardStatus::parse(&card.status).unwrap_or(CardStatus::Active),
+            status_changed_at: card.status_changed_at,
+            replaced_by: card.replaced_by,
+            replaces: card.replaces,
+            historical: false,
+            as_of: None,
+        })
+    }
+}
+
+impl ShowGetCardResponse {
+    /// Swaps the live balance for the one replayed up to `as_of`.
+    fn at(&mut self, as_of: &str, past: card_event::RunningState) {
+        self.last_total_due = Some(past.last_total_due);
+        self.last_delta = Some(past.last_delta);
+        self.utilization_percent = utilization_percent(past.last_total_due, self.credit_limit);
+        self.historical = true;
+        self.as_of = Some(as_of.to_string());
+    }
+}
+
+pub async fn load_card<'e, E>(
+    executor: E,
+    card_id: &str,
+    user_id: &str,
+) -> Result<Option<ShowGetCardResponse>, AppError>
+where
+    E: sqlx::SqliteExecutor<'e>,
+{
+    query_card_rows!(
+        "WHERE c.card_id = ? AND c.user_id = ? AND c.deleted_at IS NULL",
+        card_id,
         user_id
     )
-    .fetch_optional(&state.db)
+    .fetch_optional(executor)
     .await
     .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
-    .ok_or_else(|| {
-        AppError(
-            StatusCode::NOT_FOUND,
-            "Card not found or you don't have permission to view it".to_string(),
-        )
-    })?;
+    .map(ShowGetCardResponse::try_from)
+    .transpose()
+}
 
-    Ok(Json(ShowGetCardResponse {
-        card_id: card.card_id.unwrap(),
-        card_name: card.card_name,
-        card_bank: card.card_bank,
-        card_primary_color: unpack(card.card_primary_color),
-        card_secondary_color: unpack(card.card_secondary_color),
-        last_total_due: Some(card.last_total_due as f32),
-        last_delta: Some(card.last_delta as f32),
-    }))
+pub async fn get_card(
+    State(state): State<AppState>,
+    OwnedCard {
+        card_id,
+        user_id,
+        payload,
+        ..
+    }: OwnedCard<GetCardForUser>,
+) -> Result<Json<ShowGetCardResponse>, AppError> {
+    let as_of = payload
+        .as_of
+        .as_deref()
+        .map(|value| card_list::parse_instant("as_of", value))
+        .transpose()?;
+    let mut card = load_card(&state.db, &card_id, &user_id)
+        .await?
+        .ok_or_else(|| {
+            AppError(
+                StatusCode::NOT_FOUND,
+                "Card not found or you don't have permission to view it".to_string(),
+            )
+        })?;
+
+    if let Some(as_of) = as_of {
+        let past = card_event::state_at(&state.db, &card_id, &as_of)
+            .await
+            .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        card.at(&as_of, past);
+    }
+
+    Ok(Json(card))
 }
 
 pub async fn get_all_cards(
     State(state): State<AppState>,
     Extension((user_id, _role)): Extension<(String, String)>,
+    Query(query): Query<GetAllCardsQuery>,
 ) -> Result<impl IntoResponse, AppError> {
-    let cache_key = format!("user_cards_proto_v2:{}", user_id);
+    card_list::validate_query(&query)?;
+
+    let mut cache_key = None;
+    if let Some(mut redis) = state.redis.clone()
+        && let Some(variant) = card_list::cache_variant(&query)
+    {
+        let generation: Option<i64> = redis
+            .get(card_cache_generation_key(&user_id))
+            .await
+            .unwrap_or_default();
+        cache_key = Some(card_cache_key(
+            &user_id,
+            generation.unwrap_or_default(),
+            &variant,
+        ));
+    }
 
     // Check Redis cache if available
-    if let Some(mut redis) = state.redis.clone() {
-        if let Ok(Some(cached_data)) = redis.get::<_, Option<Vec<u8>>>(&cache_key).await {
-            return Ok(([(header::CONTENT_TYPE, "application/x-protobuf")], cached_data));
-        }
+    if let Some(mut redis) = state.redis.clone()
+        && let Some(cache_key) = &cache_key
+        && let Ok(Some(cached_data)) = redis.get::<_, Option<Vec<u8>>>(cache_key).await
+    {
+        return Ok((
+            [(header::CONTENT_TYPE, "application/x-protobuf")],
+            cached_data,
+        ));
     }
 
-    let cards = sqlx::query!(
-        "SELECT c.card_id, c.card_name, c.card_bank, c.card_primary_color, c.card_secondary_color,
-                crs.last_total_due, crs.last_delta
-         FROM cards c
-         LEFT JOIN card_running_state crs ON c.card_id = crs.card_id
-         WHERE c.user_id = ?",
-        user_id
+    let name_pattern = query.q.as_deref().map(card_list::like_pattern);
+    let status = query.status.map(CardStatus::as_str);
+    let updated_since = query
+        .updated_since
+        .as_deref()
+        .map(card_list::parse_updated_since)
+        .transpose()?;
+    let as_of = query
+        .as_of
+        .as_deref()
+        .map(|value| card_list::parse_instant("as_of", value))
+        .transpose()?;
+    // Historical balances are only known after the replay below, so a
+    // point-in-time list is filtered by amount, sorted and paged there instead.
+    let (min_due, max_due, after, limit) = if as_of.is_some() {
+        (None, None, None, -1)
+    } else {
+        (
+            query.min_due,
+            query.max_due,
+            card_list::after(&query)?,
+            card_list::fetch_limit(&query),
+        )
+    };
+    let (after_card_id, after_number, after_position, after_text) = match &after {
+        Some(after) => (
+            Some(after.card_id.as_str()),
+            Some(after.number),
+            Some(after.position),
+            Some(after.text.as_str()),
+        ),
+        None => (None, None, None, None),
+    };
+
+    let (sort, order) = (query.sort.as_str(), query.order.as_str());
+    // The sort key is three columns (see `card_list::SortKey::columns`):
+    // a number, a position and a text, each zero or empty when unused.
+    let cards = query_card_rows!(
+        r#"WHERE c.user_id = ?1 AND c.deleted_at IS NULL
+           AND (?2 OR c.archived_at IS NULL)
+           AND (?3 IS NULL OR EXISTS (
+               SELECT 1 FROM card_tags ct WHERE ct.card_id = c.card_id AND ct.tag_id = ?3
+           ))
+           AND (?4 IS NULL OR c.card_name LIKE ?4 ESCAPE '\' OR c.card_bank LIKE ?4 ESCAPE '\')
+           AND (?5 IS NULL OR COALESCE(crs.last_total_due, 0) >= ?5)
+           AND (?6 IS NULL OR COALESCE(crs.last_total_due, 0) <= ?6)
+           AND (?7 IS NULL OR crs.updated_at >= ?7)
+           AND (?8 IS NULL OR c.status = ?8)
+           AND (?11 IS NULL
+                OR (?10 = 'asc' AND (
+                    CASE ?9 WHEN 'position' THEN NOT c.pinned
+                            WHEN 'due' THEN COALESCE(crs.last_total_due, 0)
+                            WHEN 'delta' THEN COALESCE(crs.last_delta, 0)
+                            ELSE 0 END,
+                    CASE ?9 WHEN 'position' THEN c.position ELSE 0 END,
+                    CASE ?9 WHEN 'name' THEN lower(c.card_name)
+                            WHEN 'updated' THEN COALESCE(crs.updated_at, '')
+                            ELSE '' END,
+                    c.card_id) > (?12, ?13, ?14, ?11))
+                OR (?10 = 'desc' AND (
+                    CASE ?9 WHEN 'position' THEN NOT c.pinned
+                            WHEN 'due' THEN COALESCE(crs.last_total_due, 0)
+                            WHEN 'delta' THEN COALESCE(crs.last_delta, 0)
+                            ELSE 0 END,
+                    CASE ?9 WHEN 'position' THEN c.position ELSE 0 END,
+                    CASE ?9 WHEN 'name' THEN lower(c.card_name)
+                            WHEN 'updated' THEN COALESCE(crs.updated_at, '')
+                            ELSE '' END,
+                    c.card_id) < (?12, ?13, ?14, ?11)))
+         ORDER BY
+           CASE WHEN ?10 = 'asc' THEN
+               CASE ?9 WHEN 'position' THEN NOT c.pinned
+                       WHEN 'due' THEN COALESCE(crs.last_total_due, 0)
+                       WHEN 'delta' THEN COALESCE(crs.last_delta, 0)
+                       ELSE 0 END
+           END,
+           CASE WHEN ?10 = 'asc' THEN
+               CASE ?9 WHEN 'position' THEN c.position ELSE 0 END
+           END,
+           CASE WHEN ?10 = 'asc' THEN
+               CASE ?9 WHEN 'name' THEN lower(c.card_name)
+                       WHEN 'updated' THEN COALESCE(crs.updated_at, '')
+                       ELSE '' END
+           END,
+           CASE WHEN ?10 = 'asc' THEN c.card_id END,
+           CASE ?9 WHEN 'position' THEN NOT c.pinned
+                   WHEN 'due' THEN COALESCE(crs.last_total_due, 0)
+                   WHEN 'delta' THEN COALESCE(crs.last_delta, 0)
+                   ELSE 0 END DESC,
+           CASE ?9 WHEN 'position' THEN c.position ELSE 0 END DESC,
+           CASE ?9 WHEN 'name' THEN lower(c.card_name)
+                   WHEN 'updated' THEN COALESCE(crs.updated_at, '')
+                   ELSE '' END DESC,
+           c.card_id DESC
+         LIMIT ?15"#,
+        user_id,
+        query.include_archived,
+        query.tag,
+        name_pattern,
+        min_due,
+        max_due,
+        updated_since,
+        status,
+        sort,
+        order,
+        after_card_id,
+        after_number,
+        after_position,
+        after_text,
+        limit
     )
     .fetch_all(&state.db)
     .await
     .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
-    let proto_cards: Vec<crate::proto::Card> = cards
+    let mut cards = cards
         .into_iter()
-        .map(|card| crate::proto::Card {
-            card_id: card.card_id.unwrap(),
-            card_name: card.card_name,
-            card_bank: card.card_bank,
-            card_primary_color: card.card_primary_color as i32,
-            card_secondary_color: card.card_secondary_color as i32,
-            last_total_due: card.last_total_due.map(|v| v as f32),
-            last_delta: card.last_delta.map(|v| v as f32),
+        .map(ShowGetCardResponse::try_from)
+        .collect::<Result<Vec<_>, AppError>>()?;
+
+    if let Some(as_of) = &as_of {
+        let past = card_event::states_at(&state.db, &user_id, as_of)
+            .await
+            .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        for card in &mut cards {
+            card.at(as_of, past.get(&card.card_id).copied().unwrap_or_default());
+        }
+        cards.retain(|card| {
+            let total = card.last_total_due.unwrap_or_default();
+            !(query.min_due.is_some_and(|min| total < min)
+                || query.max_due.is_some_and(|max| total > max))
+        });
+    }
+
+    let proto_cards = cards
+        .into_iter()
+        .map(|card| {
+            let sort_key = card_list::sort_key(
+                query.sort,
+                card.pinned,
+                card.position,
+                card.last_total_due.unwrap_or_default(),
+                card.last_delta.unwrap_or_default(),
+                &card.card_name,
+                card.updated_at.as_deref().unwrap_or_default(),
+            );
+            (sort_key, card.card_id.clone(), card.into())
         })
         .collect();
 
-    let card_list = crate::proto::CardList { cards: proto_cards };
+    let (proto_cards, next_cursor) = if as_of.is_some() {
+        card_list::paginate(proto_cards, &query)?
+    } else {
+        card_list::page(proto_cards, &query)
+    };
+    let card_list = crate::proto::CardList {
+        cards: proto_cards,
+        next_cursor,
+    };
 
     let mut buf = Vec::new();
     card_list.encode(&mut buf).map_err(|e: prost::EncodeError| {
@@ -243,9 +1118,11 @@ pub async fn get_all_cards(
     })?;
 
     // Cache the result if Redis is available
-    if let Some(mut redis) = state.redis.clone() {
+    if let Some(mut redis) = state.redis.clone()
+        && let Some(cache_key) = cache_key
+    {
         let _: () = redis
-            .set_ex(cache_key, buf.clone(), 3600)
+            .set_ex(cache_key, buf.clone(), card_cache_ttl())
             .await
             .unwrap_or_default();
     }
@@ -255,17 +1132,35 @@ pub async fn get_all_cards(
 
 pub async fn delete_card(
     State(state): State<AppState>,
-    Extension((user_id, _role)): Extension<(String, String)>,
-    Json(card_details): Json<DeleteCardPayload>,
+    OwnedCard {
+        card_id, user_id, ..
+    }: OwnedCard<DeleteCardPayload>,
 ) -> Result<Json<CardResponse>, AppError> {
-    let card_id = card_details.card_id;
+    soft_delete_card(&state.db, &card_id, &user_id).await?;
+
+    invalidate_card_cache(&state, &user_id).await;
 
+    Ok(Json(CardResponse {
+        card_id,
+        status: true,
+    }))
+}
+
+/// Moves a card to the trash.
+pub async fn soft_delete_card<'e, E>(
+    executor: E,
+    card_id: &str,
+    user_id: &str,
+) -> Result<(), AppError>
+where
+    E: sqlx::SqliteExecutor<'e>,
+{
     let result = sqlx::query!(
-        "DELETE FROM cards WHERE card_id = ? AND user_id = ?",
+        "UPDATE cards SET deleted_at = CURRENT_TIMESTAMP WHERE card_id = ? AND user_id = ? AND deleted_at IS NULL",
         card_id,
         user_id
     )
-    .execute(&state.db)
+    .execute(executor)
     .await
     .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
@@ -275,24 +1170,339 @@ pub async fn delete_card(
             "Card not found or you don't have permission to delete it".to_string(),
         ));
     }
+    Ok(())
+}
 
-    // Invalidate cache
-    if let Some(mut redis) = state.redis.clone() {
-        let cache_key = format!("user_cards_proto_v2:{}", user_id);
-        let _: () = redis.del(cache_key).await.unwrap_or_default();
+pub async fn reorder_cards(
+    State(state): State<AppState>,
+    Extension((user_id, _role)): Extension<(String, String)>,
+    Json(payload): Json<ReorderCardsPayload>,
+) -> Result<Json<ReorderCardsResponse>, AppError> {
+    let mut tx = state.db.begin().await.map_err(|e| {
+        error!("Error starting transaction: {}", e);
+        AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+    })?;
+
+    let current_order = sqlx::query_scalar!(
+        r#"SELECT card_id as "card_id!" FROM cards
+         WHERE user_id = ? AND deleted_at IS NULL
+         ORDER BY position, rowid"#,
+        user_id
+    )
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    for (index, card_id) in payload.card_ids.iter().enumerate() {
+        if !current_order.contains(card_id) {
+            return Err(AppError(
+                StatusCode::NOT_FOUND,
+                format!(
+                    "Card {} not found or you don't have permission to move it",
+                    card_id
+                ),
+            ));
+        }
+        if payload.card_ids[..index].contains(card_id) {
+            return Err(AppError(
+                StatusCode::BAD_REQUEST,
+                format!("Card {} is listed more than once", card_id),
+            ));
+        }
+    }
+
+    // Cards left out of the request keep their relative order after the listed ones.
+    let new_order: Vec<String> = payload
+        .card_ids
+        .iter()
+        .cloned()
+        .chain(
+            current_order
+                .into_iter()
+                .filter(|card_id| !payload.card_ids.contains(card_id)),
+        )
+        .collect();
+
+    for (position, card_id) in new_order.iter().enumerate() {
+        let position = position as i64;
+        sqlx::query!(
+            "UPDATE cards SET position = ? WHERE card_id = ? AND user_id = ?",
+            position,
+            card_id,
+            user_id
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
     }
 
+    tx.commit()
+        .await
+        .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    invalidate_card_cache(&state, &user_id).await;
+
+    Ok(Json(ReorderCardsResponse {
+        card_ids: new_order,
+        status: true,
+    }))
+}
+
+pub async fn pin_card(
+    State(state): State<AppState>,
+    OwnedCard {
+        card_id,
+        user_id,
+        payload,
+        ..
+    }: OwnedCard<PinCardPayload>,
+) -> Result<Json<CardResponse>, AppError> {
+    sqlx::query!(
+        "UPDATE cards SET pinned = ? WHERE card_id = ? AND user_id = ?",
+        payload.pinned,
+        card_id,
+        user_id
+    )
+    .execute(&state.db)
+    .await
+    .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    invalidate_card_cache(&state, &user_id).await;
+
     Ok(Json(CardResponse {
         card_id,
         status: true,
     }))
 }
 
-pub async fn insert_transaction(
+pub async fn archive_card(
+    State(state): State<AppState>,
+    OwnedCard {
+        card_id, user_id, ..
+    }: OwnedCard<ArchiveCardPayload>,
+) -> Result<Json<CardResponse>, AppError> {
+    set_archived(&state, card_id, user_id, true).await
+}
+
+pub async fn unarchive_card(
+    State(state): State<AppState>,
+    OwnedCard {
+        card_id, user_id, ..
+    }: OwnedCard<ArchiveCardPayload>,
+) -> Result<Json<CardResponse>, AppError> {
+    set_archived(&state, card_id, user_id, false).await
+}
+
+async fn set_archived(
+    state: &AppState,
+    card_id: String,
+    user_id: String,
+    archived: bool,
+) -> Result<Json<CardResponse>, AppError> {
+    sqlx::query!(
+        "UPDATE cards
+         SET archived_at = CASE WHEN ? THEN COALESCE(archived_at, CURRENT_TIMESTAMP) ELSE NULL END
+         WHERE card_id = ? AND user_id = ?",
+        archived,
+        card_id,
+        user_id
+    )
+    .execute(&state.db)
+    .await
+    .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    invalidate_card_cache(state, &user_id).await;
+
+    Ok(Json(CardResponse {
+        card_id,
+        status: true,
+    }))
+}
+
+pub async fn get_trash(
     State(state): State<AppState>,
     Extension((user_id, _role)): Extension<(String, String)>,
-    Json(insert_transaction): Json<InsertTransactionPayload>,
+) -> Result<Json<Vec<TrashedCardResponse>>, AppError> {
+    let retention = format!("+{} days", state.trash_retention_days);
+    let cards = sqlx::query!(
+        r#"SELECT card_id as "card_id!", card_name, card_bank, card_primary_color, card_secondary_color,
+                deleted_at as "deleted_at!: String",
+                datetime(deleted_at, ?) as "purge_at!: String"
+         FROM cards
+         WHERE user_id = ? AND deleted_at IS NOT NULL
+         ORDER BY deleted_at DESC"#,
+        retention,
+        user_id
+    )
+    .fetch_all(&state.db)
+    .await
+    .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let trash = cards
+        .into_iter()
+        .map(|card| TrashedCardResponse {
+            card_id: card.card_id,
+            card_name: card.card_name,
+            card_bank: card.card_bank,
+            card_primary_color: unpack(card.card_primary_color),
+            card_secondary_color: unpack(card.card_secondary_color),
+            deleted_at: card.deleted_at,
+            purge_at: card.purge_at,
+        })
+        .collect();
+
+    Ok(Json(trash))
+}
+
+pub async fn restore_card(
+    State(state): State<AppState>,
+    TrashedCard(OwnedCard {
+        card_id, user_id, ..
+    }): TrashedCard<RestoreCardPayload>,
+) -> Result<Json<CardResponse>, AppError> {
+    let retention = format!("-{} days", state.trash_retention_days);
+    let result = sqlx::query!(
+        "UPDATE cards SET deleted_at = NULL
+         WHERE card_id = ? AND user_id = ? AND deleted_at >= datetime('now', ?)",
+        card_id,
+        user_id,
+        retention
+    )
+    .execute(&state.db)
+    .await
+    .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError(
+            StatusCode::GONE,
+            "Card is past its restore window and can no longer be restored".to_string(),
+        ));
+    }
+
+    invalidate_card_cache(&state, &user_id).await;
+
+    Ok(Json(CardResponse {
+        card_id,
+        status: true,
+    }))
+}
+
+pub async fn purge_card(
+    State(state): State<AppState>,
+    TrashedCard(OwnedCard {
+        card_id, user_id, ..
+    }): TrashedCard<PurgeCardPayload>,
+) -> Result<Json<CardResponse>, AppError> {
+    let result = sqlx::query!(
+        "DELETE FROM cards WHERE card_id = ? AND user_id = ? AND deleted_at IS NOT NULL",
+        card_id,
+        user_id
+    )
+    .execute(&state.db)
+    .await
+    .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError(
+            StatusCode::NOT_FOUND,
+            "Card not found or you don't have permission to purge it".to_string(),
+        ));
+    }
+
+    Ok(Json(CardResponse {
+        card_id,
+        status: true,
+    }))
+}
+
+/// Permanently removes cards whose tombstone is older than the retention
+/// window. Their events and running state go with them through the cascades.
+pub async fn purge_expired_cards(db: &SqlitePool, retention_days: i64) -> Result<u64, sqlx::Error> {
+    let retention = format!("-{} days", retention_days);
+    let result = sqlx::query!(
+        "DELETE FROM cards WHERE deleted_at IS NOT NULL AND deleted_at < datetime('now', ?)",
+        retention
+    )
+    .execute(db)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+/// Honours an `Idempotency-Key` header: the key is reserved before anything is
+/// recorded, a retry with the same key and body gets the first response back,
+/// and a retry while the first request is still running is 409.
+pub async fn insert_transaction(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    card: OwnedCard<InsertTransactionPayload>,
 ) -> Result<Json<InsertTransactionResponse>, AppError> {
+    let Some(key) = idempotency::key_from_headers(&headers)? else {
+        return record_transaction(&state, card, None).await.map(Json);
+    };
+    let reservation = match idempotency::reserve(&state, &card.user_id, &key, &card.payload).await?
+    {
+        Reserved::New(reservation) => reservation,
+        Reserved::Replay(response) => return Ok(Json(response)),
+    };
+    match record_transaction(&state, card, Some(&reservation)).await {
+        Ok(response) => {
+            idempotency::cache(&state, &reservation, &response).await;
+            Ok(Json(response))
+        }
+        Err(e) => {
+            idempotency::release(&state, &reservation).await;
+            Err(e)
+        }
+    }
+}
+
+/// Records one transaction; with a `reservation`, its response is stored in
+/// the same database transaction as the event.
+async fn record_transaction(
+    state: &AppState,
+    OwnedCard {
+        card_id,
+        user_id,
+        archived,
+        status,
+        payload: insert_transaction,
+    }: OwnedCard<InsertTransactionPayload>,
+    reservation: Option<&Reservation>,
+) -> Result<InsertTransactionResponse, AppError> {
+    if archived {
+        return Err(AppError(
+            StatusCode::CONFLICT,
+            "Card is archived; unarchive it before recording new dues".to_string(),
+        ));
+    }
+    if status == CardStatus::Closed {
+        return Err(AppError(
+            StatusCode::CONFLICT,
+            "Card is closed; record new dues on its replacement".to_string(),
+        ));
+    }
+
+    let current = card_event::current_state(&state.db, &card_id)
+        .await
+        .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let (event_type, amount) = card_event::resolve_input(
+        current,
+        insert_transaction.event_type,
+        insert_transaction.amount_due,
+        insert_transaction.amount,
+        insert_transaction.payment,
+    )?;
+    card_event::validate(current, event_type, amount, state.max_total_due)?;
+    let (merchant, category) = card_event::purchase_details(
+        event_type,
+        insert_transaction.merchant.as_deref(),
+        insert_transaction.category.as_deref(),
+    )?;
+    let next = card_event::apply(current, event_type, amount);
+    // A due snapshot is its own total; other types keep the amount entered.
+    let entered_amount = (event_type != EventType::Due).then_some(amount);
+
     let transaction_id = nanoid!();
     let mut tx = state.db.begin().await.map_err(|e| {
         error!("Error setting transaction check {} ", e);
@@ -300,10 +1510,14 @@ pub async fn insert_transaction(
     })?;
 
     sqlx::query!(
-        "INSERT INTO card_events (transaction_id, card_id, total_due_input) VALUES (?, ?, ?)",
+        "INSERT INTO card_events (transaction_id, card_id, total_due_input, event_type, amount, merchant, category) VALUES (?, ?, ?, ?, ?, ?, ?)",
         transaction_id,
-        insert_transaction.card_id,
-        insert_transaction.amount_due,
+        card_id,
+        next.last_total_due,
+        event_type,
+        entered_amount,
+        merchant,
+        category
     )
     .execute(&mut *tx)
     .await
@@ -312,57 +1526,197 @@ pub async fn insert_transaction(
         AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
     })?;
 
-    let result = sqlx::query!(
+    // Only applies if nothing was recorded since the state was read.
+    let updated = sqlx::query!(
         "UPDATE card_running_state
-         SET last_delta = ? - last_total_due,
-             last_total_due = ?,
+         SET last_total_due = ?,
+             last_delta = ?,
              updated_at = CURRENT_TIMESTAMP
-         WHERE card_id = ?
-         RETURNING last_delta",
-        insert_transaction.amount_due,
-        insert_transaction.amount_due,
-        insert_transaction.card_id
+         WHERE card_id = ? AND last_total_due = ? AND last_delta = ?",
+        next.last_total_due,
+        next.last_delta,
+        card_id,
+        current.last_total_due,
+        current.last_delta
     )
-    .fetch_one(&mut *tx)
+    .execute(&mut *tx)
     .await
     .map_err(|e| {
         error!("Error setting card running state {} ", e);
         AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
     })?;
+    if updated.rows_affected() == 0 {
+        return Err(AppError(
+            StatusCode::CONFLICT,
+            "The total due changed in the meantime; reload and try again".to_string(),
+        ));
+    }
+
+    let credit_limit = sqlx::query_scalar!(
+        r#"SELECT credit_limit as "credit_limit: Money" FROM cards WHERE card_id = ?"#,
+        card_id
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let thresholds = load_utilization_thresholds(&mut *tx, &card_id).await?;
+
+    let utilization = utilization_percent(next.last_total_due, credit_limit);
+    let previous_utilization = utilization_percent(current.last_total_due, credit_limit);
+    let warnings = match (previous_utilization, utilization) {
+        (Some(previous), Some(current)) => thresholds
+            .into_iter()
+            .filter(|threshold| previous < *threshold && current >= *threshold)
+            .map(|threshold| UtilizationWarning {
+                threshold_percent: threshold,
+                utilization_percent: current,
+                message: format!(
+                    "Utilization crossed {}% and is now {:.1}%",
+                    threshold, current
+                ),
+            })
+            .collect(),
+        _ => Vec::new(),
+    };
+
+    let response = InsertTransactionResponse {
+        transaction_id,
+        amount_due: next.last_delta,
+        event_type,
+        utilization_percent: utilization,
+        warnings,
+        status: true,
+    };
+    if let Some(reservation) = reservation {
+        idempotency::complete(&mut tx, reservation, &response).await?;
+    }
 
-    let last_delta = result.last_delta;
     tx.commit()
         .await
         .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
-    // Invalidate cache
-    if let Some(mut redis) = state.redis.clone() {
-        let cache_key = format!("user_cards_proto_v2:{}", user_id);
-        let _: () = redis.del(cache_key).await.unwrap_or_default();
+    invalidate_card_cache(state, &user_id).await;
+
+    Ok(response)
+}
+
+pub async fn set_utilization_thresholds(
+    State(state): State<AppState>,
+    OwnedCard {
+        card_id, payload, ..
+    }: OwnedCard<SetUtilizationThresholdsPayload>,
+) -> Result<Json<UtilizationThresholdsResponse>, AppError> {
+    if payload
+        .thresholds
+        .iter()
+        .any(|threshold| !threshold.is_finite() || *threshold <= 0.0 || *threshold > 100.0)
+    {
+        return Err(AppError(
+            StatusCode::BAD_REQUEST,
+            "Utilization thresholds must be between 0 and 100 percent".to_string(),
+        ));
     }
 
-    Ok(Json(InsertTransactionResponse {
-        transaction_id,
-        amount_due: last_delta as f32,
-        status: true,
+    let mut tx = state.db.begin().await.map_err(|e| {
+        error!("Error starting transaction: {}", e);
+        AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+    })?;
+
+    sqlx::query!(
+        "DELETE FROM card_utilization_thresholds WHERE card_id = ?",
+        card_id
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    for threshold in &payload.thresholds {
+        sqlx::query!(
+            "INSERT OR IGNORE INTO card_utilization_thresholds (card_id, threshold_percent) VALUES (?, ?)",
+            card_id,
+            threshold
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    }
+
+    let thresholds = load_utilization_thresholds(&mut *tx, &card_id).await?;
+
+    tx.commit()
+        .await
+        .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(UtilizationThresholdsResponse {
+        card_id,
+        thresholds,
     }))
 }
 
+pub async fn get_utilization_thresholds(
+    State(state): State<AppState>,
+    OwnedCard { card_id, .. }: OwnedCard<GetCardForUser>,
+) -> Result<Json<UtilizationThresholdsResponse>, AppError> {
+    let thresholds = load_utilization_thresholds(&state.db, &card_id).await?;
+
+    Ok(Json(UtilizationThresholdsResponse {
+        card_id,
+        thresholds,
+    }))
+}
+
+async fn load_utilization_thresholds<'e, E>(
+    executor: E,
+    card_id: &str,
+) -> Result<Vec<f32>, AppError>
+where
+    E: sqlx::SqliteExecutor<'e>,
+{
+    let thresholds = sqlx::query_scalar!(
+        "SELECT threshold_percent FROM card_utilization_thresholds WHERE card_id = ? ORDER BY threshold_percent",
+        card_id
+    )
+    .fetch_all(executor)
+    .await
+    .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(thresholds.into_iter().map(|v| v as f32).collect())
+}
+
 pub async fn get_history(
     State(state): State<AppState>,
-    Extension((_user_id, _role)): Extension<(String, String)>,
-    Json(payload): Json<crate::models::GetHistoryPayload>,
+    OwnedCard {
+        card_id, payload, ..
+    }: OwnedCard<crate::models::GetHistoryPayload>,
 ) -> Result<impl IntoResponse, AppError> {
+    let currency = sqlx::query_scalar!("SELECT currency FROM cards WHERE card_id = ?", card_id)
+        .fetch_one(&state.db)
+        .await
+        .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
     let history = sqlx::query_as!(
         crate::models::CardTransactionHistory,
         r#"
+        WITH RECURSIVE chain(card_id) AS (
+            SELECT ?
+            UNION
+            SELECT c.card_id FROM cards c JOIN chain ON c.replaced_by = chain.card_id
+            WHERE ?
+        )
         SELECT transaction_id as "transaction_id!",
-        total_due_input as "total_due_input!: f32",
-        timestamp as "timestamp!: String"
+        card_id as "card_id!",
+        total_due_input as "total_due_input!: Money",
+        timestamp as "timestamp!: String",
+        event_type as "event_type!: EventType",
+        amount as "amount: Money",
+        merchant,
+        category
         FROM card_events
-        WHERE card_id = ?
-        ORDER BY timestamp DESC"#,
-        payload.card_id
+        WHERE card_id IN (SELECT card_id FROM chain)
+        ORDER BY timestamp DESC, rowid DESC"#,
+        card_id,
+        payload.include_predecessors
     )
     .fetch_all(&state.db)
     .await
@@ -373,6 +1727,7 @@ pub async fn get_history(
 
     let response = crate::proto::CardHistoryList {
         histories: proto_histories,
+        currency,
     };
 
     let mut buf = Vec::new();
@@ -385,24 +1740,17 @@ pub async fn get_history(
 
 pub async fn reset_transactions(
     State(state): State<AppState>,
-    Extension((user_id, _role)): Extension<(String, String)>,
-    Json(payload): Json<ResetTransactionsPayload>,
-) -> Result<Json<CardResponse>, AppError> {
-    let card_id = &payload.card_id;
-
-    let card_exists = sqlx::query!(
-        "SELECT card_id FROM cards WHERE card_id = ? AND user_id = ?",
+    OwnedCard {
         card_id,
-        user_id
-    )
-    .fetch_optional(&state.db)
-    .await
-    .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
-
-    if card_exists.is_none() {
+        user_id,
+        archived,
+        ..
+    }: OwnedCard<ResetTransactionsPayload>,
+) -> Result<Json<CardResponse>, AppError> {
+    if archived {
         return Err(AppError(
-            StatusCode::NOT_FOUND,
-            "Card not found or you don't have permission to reset it".to_string(),
+            StatusCode::CONFLICT,
+            "Card is archived; its history can't be reset".to_string(),
         ));
     }
 
@@ -435,13 +1783,10 @@ pub async fn reset_transactions(
         AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
     })?;
 
-    if let Some(mut redis) = state.redis.clone() {
-        let cache_key = format!("user_cards_proto_v2:{}", user_id);
-        let _: () = redis.del(cache_key).await.unwrap_or_default();
-    }
+    invalidate_card_cache(&state, &user_id).await;
 
     Ok(Json(CardResponse {
-        card_id: card_id.to_string(),
+        card_id,
         status: true,
     }))
 }
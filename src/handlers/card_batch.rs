@@ -0,0 +1,115 @@
+use axum::{extract::State, http::StatusCode, Extension, Json};
+use sqlx::Connection;
+use tracing::error;
+
+use crate::{
+    handlers::{
+        card::{apply_card_update, insert_card, invalidate_card_cache, soft_delete_card},
+        common::AppError,
+    },
+    models::{
+        AppState, BatchMode, CardBatchPayload, CardBatchResponse, CardOperation,
+        CardOperationResult,
+    },
+};
+
+pub const MAX_BATCH_OPERATIONS: usize = 100;
+
+fn internal_error(e: sqlx::Error) -> AppError {
+    error!("Error running card batch {}", e);
+    AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+}
+
+/// Runs card creates, updates and deletes in one transaction. Each operation
+/// gets its own savepoint, so every operation is attempted and reported even in
+/// all-or-nothing mode; the mode only decides whether a failure rolls back the
+/// rest. The card list cache is invalidated once, after the commit.
+pub async fn batch_cards(
+    State(state): State<AppState>,
+    Extension((user_id, _role)): Extension<(String, String)>,
+    Json(payload): Json<CardBatchPayload>,
+) -> Result<Json<CardBatchResponse>, AppError> {
+    if payload.operations.is_empty() || payload.operations.len() > MAX_BATCH_OPERATIONS {
+        return Err(AppError(
+            StatusCode::BAD_REQUEST,
+            format!(
+                "A batch must contain between 1 and {} operations",
+                MAX_BATCH_OPERATIONS
+            ),
+        ));
+    }
+
+    let mut tx = state.db.begin().await.map_err(internal_error)?;
+
+    let mut results = Vec::with_capacity(payload.operations.len());
+    for (index, operation) in payload.operations.into_iter().enumerate() {
+        let op = operation.as_str();
+        let mut savepoint = tx.begin().await.map_err(internal_error)?;
+        let (card_id, outcome) = match operation {
+            CardOperation::Create(card_details) => {
+                match insert_card(&mut savepoint, &user_id, card_details).await {
+                    Ok(card_id) => (Some(card_id), Ok(())),
+                    Err(e) => (None, Err(e)),
+                }
+            }
+            CardOperation::Update(update_card_details) => {
+                let card_id = update_card_details.card_id.clone();
+                let outcome =
+                    apply_card_update(&mut savepoint, &card_id, &user_id, update_card_details)
+                        .await
+                        .map(|_| ());
+                (Some(card_id), outcome)
+            }
+            CardOperation::Delete(delete) => {
+                let outcome = soft_delete_card(&mut *savepoint, &delete.card_id, &user_id).await;
+                (Some(delete.card_id), outcome)
+            }
+        };
+
+        match outcome {
+            Ok(()) => {
+                savepoint.commit().await.map_err(internal_error)?;
+                results.push(CardOperationResult {
+                    index,
+                    op,
+                    card_id,
+                    status: StatusCode::OK.as_u16(),
+                    error: None,
+                });
+            }
+            Err(AppError(status, message)) => {
+                savepoint.rollback().await.map_err(internal_error)?;
+                results.push(CardOperationResult {
+                    index,
+                    op,
+                    card_id,
+                    status: status.as_u16(),
+                    error: Some(message),
+                });
+            }
+        }
+    }
+
+    let failed = results
+        .iter()
+        .filter(|result| result.error.is_some())
+        .count();
+    let succeeded = results.len() - failed;
+    let committed = failed == 0 || payload.mode == BatchMode::BestEffort;
+    if committed {
+        tx.commit().await.map_err(internal_error)?;
+    } else {
+        tx.rollback().await.map_err(internal_error)?;
+    }
+
+    if committed && succeeded > 0 {
+        invalidate_card_cache(&state, &user_id).await;
+    }
+
+    Ok(Json(CardBatchResponse {
+        committed,
+        succeeded,
+        failed,
+        results,
+    }))
+}
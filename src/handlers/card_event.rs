@@ -0,0 +1,994 @@
+use std::collections::HashMap;
+
+use axum::{extract::State, http::StatusCode, Json};
+use sqlx::{SqliteConnection, SqliteExecutor, SqlitePool};
+use tracing::error;
+
+use crate::{
+    extractors::OwnedCard,
+    handlers::{card::invalidate_card_cache, card_list, common::AppError},
+    models::{
+        AppState, DeleteTransactionPayload, EventType, RebuildRunningStatePayload,
+        RebuildRunningStateResponse, RunningStateMismatch, RunningStateResponse,
+        UndoTransactionPayload, UpdateTransactionPayload,
+    },
+    money::Money,
+};
+
+const MAX_MERCHANT_LEN: usize = 100;
+const MAX_CATEGORY_LEN: usize = 40;
+
+/// A card's `card_running_state` values.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct RunningState {
+    pub last_total_due: Money,
+    pub last_delta: Money,
+}
+
+fn bad_request(message: String) -> AppError {
+    AppError(StatusCode::BAD_REQUEST, message)
+}
+
+fn unprocessable(message: String) -> AppError {
+    AppError(StatusCode::UNPROCESSABLE_ENTITY, message)
+}
+
+fn internal_error(e: sqlx::Error) -> AppError {
+    error!("Error replaying card events {}", e);
+    AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+}
+
+/// A `card_events` row as far as the running state is concerned.
+pub struct StoredEvent {
+    pub transaction_id: String,
+    pub timestamp: String,
+    pub event_type: EventType,
+    pub amount: Option<Money>,
+    pub total_due_input: Money,
+}
+
+impl StoredEvent {
+    /// What `apply` takes: the snapshot for `due`, the amount otherwise.
+    fn input(&self) -> Money {
+        match self.event_type {
+            EventType::Due => self.total_due_input,
+            _ => self.amount.unwrap_or_default(),
+        }
+    }
+}
+
+/// The state after an event. `amount` is the new total for a `due` snapshot
+/// and the event's own amount for every other type.
+pub fn apply(state: RunningState, event_type: EventType, amount: Money) -> RunningState {
+    let (last_total_due, last_delta) = match event_type {
+        EventType::Due => (amount, amount - state.last_total_due),
+        EventType::Payment => (state.last_total_due - amount, Money::ZERO),
+        EventType::Refund => (state.last_total_due - amount, -amount),
+        EventType::Fee | EventType::Purchase => (state.last_total_due + amount, amount),
+        EventType::Adjustment => (state.last_total_due + amount, Money::ZERO),
+    };
+    RunningState {
+        last_total_due,
+        last_delta,
+    }
+}
+
+/// Checks that an amount has the right sign for its type and that a snapshot
+/// isn't above `max_total_due`.
+pub fn validate_amount(
+    event_type: EventType,
+    amount: Money,
+    max_total_due: Money,
+) -> Result<(), AppError> {
+    match event_type {
+        EventType::Due if amount.is_negative() => {
+            return Err(bad_request("amount_due can't be negative".to_string()));
+        }
+        EventType::Payment | EventType::Refund | EventType::Fee | EventType::Purchase
+            if !amount.is_positive() =>
+        {
+            return Err(bad_request(format!(
+                "A {} needs a positive amount",
+                event_type.as_str()
+            )));
+        }
+        EventType::Adjustment if amount == Money::ZERO => {
+            return Err(bad_request(
+                "An adjustment needs a non-zero amount".to_string(),
+            ));
+        }
+        _ => {}
+    }
+    if event_type == EventType::Due && amount > max_total_due {
+        return Err(bad_request(format!(
+            "amount_due can't exceed {}",
+            max_total_due
+        )));
+    }
+    Ok(())
+}
+
+/// Checks an event against the state it applies to: amounts have the right
+/// sign (400) and the total due stays between zero and `max_total_due` and
+/// only goes down through a payment, refund or adjustment (422).
+pub fn validate(
+    state: RunningState,
+    event_type: EventType,
+    amount: Money,
+    max_total_due: Money,
+) -> Result<(), AppError> {
+    validate_amount(event_type, amount, max_total_due)?;
+
+    let total = apply(state, event_type, amount).last_total_due;
+    if event_type == EventType::Due && total < state.last_total_due {
+        return Err(unprocessable(format!(
+            "amount_due is lower than the current total due of {}; set payment to true to record a payment",
+            state.last_total_due
+        )));
+    }
+    if total.is_negative() {
+        return Err(unprocessable(format!(
+            "A {} of {} is more than the current total due of {}",
+            event_type.as_str(),
+            amount,
+            state.last_total_due
+        )));
+    }
+    if total > max_total_due {
+        return Err(unprocessable(format!(
+            "This {} would take the total due past the maximum of {}",
+            event_type.as_str(),
+            max_total_due
+        )));
+    }
+    Ok(())
+}
+
+/// Works out the event type and amount from an `insert_transaction` request.
+/// A `due` snapshot sent with `payment` becomes a payment of the difference.
+pub fn resolve_input(
+    state: RunningState,
+    event_type: EventType,
+    amount_due: Option<Money>,
+    amount: Option<Money>,
+    payment: bool,
+) -> Result<(EventType, Money), AppError> {
+    match (event_type, amount_due, amount) {
+        (EventType::Due, Some(amount_due), None) if payment => {
+            if amount_due.is_negative() || amount_due >= state.last_total_due {
+                return Err(unprocessable(format!(
+                    "A payment must lower the total due below the current {}",
+                    state.last_total_due
+                )));
+            }
+            Ok((EventType::Payment, state.last_total_due - amount_due))
+        }
+        (EventType::Due, Some(amount_due), None) => Ok((EventType::Due, amount_due)),
+        (EventType::Due, _, _) => Err(bad_request(
+            "A due event needs amount_due, the card's new total due".to_string(),
+        )),
+        (_, _, _) if payment => Err(bad_request(
+            "payment only applies to due events".to_string(),
+        )),
+        (event_type, None, Some(amount)) => Ok((event_type, amount)),
+        (event_type, _, _) => Err(bad_request(format!(
+            "A {} event needs amount instead of amount_due",
+            event_type.as_str()
+        ))),
+    }
+}
+
+fn optional_text(
+    field: &str,
+    value: Option<&str>,
+    max_len: usize,
+) -> Result<Option<String>, AppError> {
+    let Some(value) = value.map(str::trim).filter(|value| !value.is_empty()) else {
+        return Ok(None);
+    };
+    if value.chars().count() > max_len {
+        return Err(bad_request(format!(
+            "{} can be at most {} characters",
+            field, max_len
+        )));
+    }
+    Ok(Some(value.to_string()))
+}
+
+/// Checks the merchant and category of an event. Purchases need a merchant;
+/// other types can't have either.
+pub fn purchase_details(
+    event_type: EventType,
+    merchant: Option<&str>,
+    category: Option<&str>,
+) -> Result<(Option<String>, Option<String>), AppError> {
+    let merchant = optional_text("merchant", merchant, MAX_MERCHANT_LEN)?;
+    let category = optional_text("category", category, MAX_CATEGORY_LEN)?;
+    match event_type {
+        EventType::Purchase if merchant.is_none() => {
+            Err(bad_request("A purchase needs a merchant".to_string()))
+        }
+        EventType::Purchase => Ok((merchant, category)),
+        _ if merchant.is_some() || category.is_some() => Err(bad_request(
+            "Only purchases have a merchant or category".to_string(),
+        )),
+        _ => Ok((None, None)),
+    }
+}
+
+/// The card's events in the order they apply: by timestamp, then by insertion
+/// for events recorded in the same second.
+pub async fn load_events(
+    conn: &mut SqliteConnection,
+    card_id: &str,
+) -> Result<Vec<StoredEvent>, sqlx::Error> {
+    sqlx::query_as!(
+        StoredEvent,
+        r#"SELECT transaction_id as "transaction_id!",
+                timestamp as "timestamp!: String",
+                event_type as "event_type!: EventType",
+                amount as "amount: Money",
+                total_due_input as "total_due_input!: Money"
+         FROM card_events
+         WHERE card_id = ?
+         ORDER BY timestamp, rowid"#,
+        card_id
+    )
+    .fetch_all(conn)
+    .await
+}
+
+/// The card's state as it stood at `as_of` (SQLite `YYYY-MM-DD HH:MM:SS`),
+/// replayed from the events up to and including that instant.
+pub async fn state_at<'e>(
+    db: impl SqliteExecutor<'e>,
+    card_id: &str,
+    as_of: &str,
+) -> Result<RunningState, sqlx::Error> {
+    let events = sqlx::query_as!(
+        StoredEvent,
+        r#"SELECT transaction_id as "transaction_id!",
+                timestamp as "timestamp!: String",
+                event_type as "event_type!: EventType",
+                amount as "amount: Money",
+                total_due_input as "total_due_input!: Money"
+         FROM card_events INDEXED BY idx_card_events_card_id_timestamp
+         WHERE card_id = ? AND timestamp <= ?
+         ORDER BY timestamp, rowid"#,
+        card_id,
+        as_of
+    )
+    .fetch_all(db)
+    .await?;
+    Ok(replay(&events).0)
+}
+
+/// `state_at` for all of a user's cards in one query. Cards with no events up
+/// to `as_of` are left out; their state is the default.
+pub async fn states_at(
+    db: &SqlitePool,
+    user_id: &str,
+    as_of: &str,
+) -> Result<HashMap<String, RunningState>, sqlx::Error> {
+    let rows = sqlx::query!(
+        r#"SELECT e.card_id as "card_id!",
+                e.transaction_id as "transaction_id!",
+                e.timestamp as "timestamp!: String",
+                e.event_type as "event_type!: EventType",
+                e.amount as "amount: Money",
+                e.total_due_input as "total_due_input!: Money"
+         FROM card_events e
+         JOIN cards c ON c.card_id = e.card_id
+         WHERE c.user_id = ? AND c.deleted_at IS NULL AND e.timestamp <= ?
+         ORDER BY e.card_id, e.timestamp, e.rowid"#,
+        user_id,
+        as_of
+    )
+    .fetch_all(db)
+    .await?;
+
+    let mut states: HashMap<String, RunningState> = HashMap::new();
+    for row in rows {
+        let event = StoredEvent {
+            transaction_id: row.transaction_id,
+            timestamp: row.timestamp,
+            event_type: row.event_type,
+            amount: row.amount,
+            total_due_input: row.total_due_input,
+        };
+        let state = states.entry(row.card_id).or_default();
+        *state = apply(*state, event.event_type, event.input());
+    }
+    Ok(states)
+}
+
+/// Applies `events` from an empty card. Returns the final state and the total
+/// due after each event.
+pub fn replay(events: &[StoredEvent]) -> (RunningState, Vec<Money>) {
+    let mut state = RunningState::default();
+    let totals = events
+        .iter()
+        .map(|event| {
+            state = apply(state, event.event_type, event.input());
+            state.last_total_due
+        })
+        .collect();
+    (state, totals)
+}
+
+/// Writes a card's running state, creating the row if it is missing.
+pub async fn write_state(
+    conn: &mut SqliteConnection,
+    card_id: &str,
+    state: RunningState,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "INSERT INTO card_running_state (card_id, last_total_due, last_delta) VALUES (?, ?, ?)
+         ON CONFLICT (card_id) DO UPDATE
+         SET last_total_due = excluded.last_total_due,
+             last_delta = excluded.last_delta,
+             updated_at = CURRENT_TIMESTAMP",
+        card_id,
+        state.last_total_due,
+        state.last_delta
+    )
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+/// Starts a card that has no events yet at `balance`, recorded as an
+/// adjustment so that replaying the card's events still ends at that total.
+/// Nothing is recorded for a zero balance.
+pub async fn record_opening_balance(
+    conn: &mut SqliteConnection,
+    card_id: &str,
+    balance: Money,
+) -> Result<(), sqlx::Error> {
+    if balance == Money::ZERO {
+        return Ok(());
+    }
+    let (transaction_id, event_type) = (nanoid::nanoid!(), EventType::Adjustment);
+    sqlx::query!(
+        "INSERT INTO card_events (transaction_id, card_id, total_due_input, event_type, amount) VALUES (?, ?, ?, ?, ?)",
+        transaction_id,
+        card_id,
+        balance,
+        event_type,
+        balance
+    )
+    .execute(&mut *conn)
+    .await?;
+    write_state(
+        conn,
+        card_id,
+        apply(RunningState::default(), event_type, balance),
+    )
+    .await
+}
+
+/// The card's stored running state. A card without a running-state row gets
+/// one rebuilt from its events.
+pub async fn current_state(db: &SqlitePool, card_id: &str) -> Result<RunningState, sqlx::Error> {
+    let stored = sqlx::query_as!(
+        RunningState,
+        r#"SELECT last_total_due as "last_total_due!: Money", last_delta as "last_delta!: Money"
+         FROM card_running_state WHERE card_id = ?"#,
+        card_id
+    )
+    .fetch_optional(db)
+    .await?;
+    if let Some(stored) = stored {
+        return Ok(stored);
+    }
+
+    let mut conn = db.acquire().await?;
+    let (replayed, _) = replay(&load_events(&mut conn, card_id).await?);
+    write_state(&mut conn, card_id, replayed).await?;
+    Ok(replayed)
+}
+
+/// Replays the card's events after one was changed: refreshes each event's
+/// resulting total and the running state. `changed_from` is the earliest
+/// timestamp the change touched; from there on, each event has to pass the
+/// rules it was recorded under, so the total due stays between 0 and
+/// `max_total_due` and a `due` snapshot isn't below the total before it (422).
+pub async fn recompute(
+    conn: &mut SqliteConnection,
+    card_id: &str,
+    changed_from: &str,
+    max_total_due: Money,
+) -> Result<RunningState, AppError> {
+    let events = load_events(&mut *conn, card_id)
+        .await
+        .map_err(internal_error)?;
+    let (state, totals) = replay(&events);
+
+    let mut before = Money::ZERO;
+    for (event, &total) in events.iter().zip(&totals) {
+        let previous = std::mem::replace(&mut before, total);
+        if event.timestamp.as_str() < changed_from {
+            continue;
+        }
+        if event.event_type == EventType::Due && total < previous {
+            return Err(unprocessable(format!(
+                "This change would leave the due snapshot {} of {} below the total due of {} before it; record the difference as a payment",
+                event.transaction_id, total, previous
+            )));
+        }
+        if total.is_negative() || total > max_total_due {
+            return Err(unprocessable(format!(
+                "This change would take the total due to {} at transaction {}; it must stay between 0.00 and {}",
+                total, event.transaction_id, max_total_due
+            )));
+        }
+    }
+
+    for (event, total) in events.iter().zip(totals) {
+        if event.total_due_input != total {
+            sqlx::query!(
+                "UPDATE card_events SET total_due_input = ? WHERE transaction_id = ?",
+                total,
+                event.transaction_id
+            )
+            .execute(&mut *conn)
+            .await
+            .map_err(internal_error)?;
+        }
+    }
+    write_state(conn, card_id, state)
+        .await
+        .map_err(internal_error)?;
+    Ok(state)
+}
+
+fn transaction_not_found() -> AppError {
+    AppError(StatusCode::NOT_FOUND, "Transaction not found".to_string())
+}
+
+fn ensure_editable(archived: bool) -> Result<(), AppError> {
+    if archived {
+        return Err(AppError(
+            StatusCode::CONFLICT,
+            "Card is archived; its history can't be changed".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Events up to the end of the card's last statement belong to that closed
+/// cycle; they can't be changed, and no event can be moved back into it.
+async fn ensure_open_cycle(
+    conn: &mut SqliteConnection,
+    card_id: &str,
+    timestamp: &str,
+) -> Result<(), AppError> {
+    let closed_through = sqlx::query_scalar!(
+        r#"SELECT MAX(cycle_end) as "cycle_end?: String" FROM card_statements WHERE card_id = ?"#,
+        card_id
+    )
+    .fetch_one(conn)
+    .await
+    .map_err(internal_error)?;
+    if let Some(closed_through) = closed_through
+        && timestamp <= closed_through.as_str()
+    {
+        return Err(AppError(
+            StatusCode::CONFLICT,
+            format!(
+                "{} falls in a statement cycle that closed at {}; its transactions can't be changed",
+                timestamp, closed_through
+            ),
+        ));
+    }
+    Ok(())
+}
+
+async fn finish(
+    state: &AppState,
+    mut tx: sqlx::Transaction<'_, sqlx::Sqlite>,
+    card_id: String,
+    user_id: &str,
+    changed_from: &str,
+) -> Result<Json<RunningStateResponse>, AppError> {
+    let running = recompute(&mut tx, &card_id, changed_from, state.max_total_due).await?;
+    tx.commit().await.map_err(internal_error)?;
+    invalidate_card_cache(state, user_id).await;
+    Ok(Json(RunningStateResponse {
+        card_id,
+        last_total_due: running.last_total_due,
+        last_delta: running.last_delta,
+    }))
+}
+
+/// Changes the amount or timestamp of one event and replays the card's
+/// history so every later total follows.
+pub async fn update_transaction(
+    State(state): State<AppState>,
+    OwnedCard {
+        card_id,
+        user_id,
+        archived,
+        payload,
+        ..
+    }: OwnedCard<UpdateTransactionPayload>,
+) -> Result<Json<RunningStateResponse>, AppError> {
+    ensure_editable(archived)?;
+    if payload.amount.is_none() && payload.timestamp.is_none() {
+        return Err(bad_request(
+            "Nothing to update; pass amount or timestamp".to_string(),
+        ));
+    }
+    let timestamp = payload
+        .timestamp
+        .as_deref()
+        .map(|value| card_list::parse_instant("timestamp", value))
+        .transpose()?;
+
+    let mut tx = state.db.begin().await.map_err(internal_error)?;
+    let event = sqlx::query!(
+        r#"SELECT event_type as "event_type!: EventType", timestamp as "timestamp!: String"
+         FROM card_events WHERE transaction_id = ? AND card_id = ?"#,
+        payload.transaction_id,
+        card_id
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(internal_error)?
+    .ok_or_else(transaction_not_found)?;
+    if let Some(amount) = payload.amount {
+        validate_amount(event.event_type, amount, state.max_total_due)?;
+    }
+    ensure_open_cycle(&mut tx, &card_id, &event.timestamp).await?;
+    if let Some(timestamp) = &timestamp {
+        ensure_open_cycle(&mut tx, &card_id, timestamp).await?;
+    }
+
+    // A snapshot's amount is its total; other types keep the amount entered.
+    sqlx::query!(
+        "UPDATE card_events
+         SET total_due_input = CASE WHEN event_type = 'due' THEN COALESCE(?, total_due_input) ELSE total_due_input END,
+             amount = CASE WHEN event_type = 'due' THEN amount ELSE COALESCE(?, amount) END,
+             timestamp = COALESCE(?, timestamp)
+         WHERE transaction_id = ?",
+        payload.amount,
+        payload.amount,
+        timestamp,
+        payload.transaction_id
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(internal_error)?;
+
+    let changed_from = match timestamp {
+        Some(timestamp) if timestamp < event.timestamp => timestamp,
+        _ => event.timestamp,
+    };
+    finish(&state, tx, card_id, &user_id, &changed_from).await
+}
+
+/// Removes one event and replays the card's history without it.
+pub async fn delete_transaction(
+    State(state): State<AppState>,
+    OwnedCard {
+        card_id,
+        user_id,
+        archived,
+        payload,
+        ..
+    }: OwnedCard<DeleteTransactionPayload>,
+) -> Result<Json<RunningStateResponse>, AppError> {
+    ensure_editable(archived)?;
+
+    let mut tx = state.db.begin().await.map_err(internal_error)?;
+    let timestamp = sqlx::query_scalar!(
+        r#"DELETE FROM card_events WHERE transaction_id = ? AND card_id = ?
+         RETURNING timestamp as "timestamp!: String""#,
+        payload.transaction_id,
+        card_id
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(internal_error)?
+    .ok_or_else(transaction_not_found)?;
+    ensure_open_cycle(&mut tx, &card_id, &timestamp).await?;
+
+    finish(&state, tx, card_id, &user_id, &timestamp).await
+}
+
+/// Removes the card's most recent event, putting the running state back to
+/// what it was before it. Only applies if `transaction_id` is still the latest
+/// event, so two devices can't each undo something different.
+pub async fn undo_transaction(
+    State(state): State<AppState>,
+    OwnedCard {
+        card_id,
+        user_id,
+        archived,
+        payload,
+        ..
+    }: OwnedCard<UndoTransactionPayload>,
+) -> Result<Json<RunningStateResponse>, AppError> {
+    ensure_editable(archived)?;
+
+    let mut tx = state.db.begin().await.map_err(internal_error)?;
+    let undone = sqlx::query_scalar!(
+        r#"DELETE FROM card_events
+         WHERE transaction_id = ? AND card_id = ?
+           AND transaction_id = (
+               SELECT transaction_id FROM card_events
+               WHERE card_id = ?
+               ORDER BY timestamp DESC, rowid DESC
+               LIMIT 1
+           )
+         RETURNING timestamp as "timestamp!: String""#,
+        payload.transaction_id,
+        card_id,
+        card_id
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(internal_error)?;
+
+    let Some(timestamp) = undone else {
+        let latest = sqlx::query_scalar!(
+            r#"SELECT transaction_id as "transaction_id!" FROM card_events
+             WHERE card_id = ?
+             ORDER BY timestamp DESC, rowid DESC
+             LIMIT 1"#,
+            card_id
+        )
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(internal_error)?;
+        return Err(match latest {
+            None => AppError(
+                StatusCode::NOT_FOUND,
+                "Card has no transactions to undo".to_string(),
+            ),
+            Some(latest) => AppError(
+                StatusCode::CONFLICT,
+                format!(
+                    "{} is not the latest transaction (that is {}); reload and try again",
+                    payload.transaction_id, latest
+                ),
+            ),
+        });
+    };
+    ensure_open_cycle(&mut tx, &card_id, &timestamp).await?;
+
+    finish(&state, tx, card_id, &user_id, &timestamp).await
+}
+
+/// Replays one card's events and compares the result with its stored running
+/// state and per-event totals. Unless `dry_run`, fixes whatever differs and
+/// creates the running-state row if it is missing.
+async fn check_card(
+    conn: &mut SqliteConnection,
+    card_id: &str,
+    user_id: &str,
+    dry_run: bool,
+) -> Result<Option<RunningStateMismatch>, sqlx::Error> {
+    let stored = sqlx::query_as!(
+        RunningState,
+        r#"SELECT last_total_due as "last_total_due!: Money", last_delta as "last_delta!: Money"
+         FROM card_running_state WHERE card_id = ?"#,
+        card_id
+    )
+    .fetch_optional(&mut *conn)
+    .await?;
+    let events = load_events(&mut *conn, card_id).await?;
+    let (replayed, totals) = replay(&events);
+    let stale: Vec<(&StoredEvent, Money)> = events
+        .iter()
+        .zip(totals)
+        .filter(|(event, total)| event.total_due_input != *total)
+        .collect();
+    if stored == Some(replayed) && stale.is_empty() {
+        return Ok(None);
+    }
+
+    if !dry_run {
+        for (event, total) in &stale {
+            sqlx::query!(
+                "UPDATE card_events SET total_due_input = ? WHERE transaction_id = ?",
+                total,
+                event.transaction_id
+            )
+            .execute(&mut *conn)
+            .await?;
+        }
+        write_state(conn, card_id, replayed).await?;
+    }
+
+    Ok(Some(RunningStateMismatch {
+        card_id: card_id.to_string(),
+        user_id: user_id.to_string(),
+        stored_total_due: stored.map(|state| state.last_total_due),
+        stored_delta: stored.map(|state| state.last_delta),
+        replayed_total_due: replayed.last_total_due,
+        replayed_delta: replayed.last_delta,
+        stale_events: stale.len(),
+        repaired: !dry_run,
+    }))
+}
+
+/// Checks (and unless `dry_run`, repairs) the running state of one card or of
+/// every card, deleted ones included. Returns how many cards were checked and
+/// the ones that didn't match. Each card is repaired in its own transaction.
+pub async fn rebuild_running_states(
+    db: &SqlitePool,
+    card_id: Option<&str>,
+    dry_run: bool,
+) -> Result<(usize, Vec<RunningStateMismatch>), sqlx::Error> {
+    let cards = sqlx::query!(
+        r#"SELECT card_id as "card_id!", user_id FROM cards WHERE ? IS NULL OR card_id = ? ORDER BY card_id"#,
+        card_id,
+        card_id
+    )
+    .fetch_all(db)
+    .await?;
+
+    let mut mismatches = Vec::new();
+    for card in &cards {
+        let mut tx = db.begin().await?;
+        if let Some(mismatch) = check_card(&mut tx, &card.card_id, &card.user_id, dry_run).await? {
+            mismatches.push(mismatch);
+        }
+        tx.commit().await?;
+    }
+    Ok((cards.len(), mismatches))
+}
+
+/// Admin endpoint over `rebuild_running_states`; dry run unless told otherwise.
+pub async fn rebuild_running_state(
+    State(state): State<AppState>,
+    Json(payload): Json<RebuildRunningStatePayload>,
+) -> Result<Json<RebuildRunningStateResponse>, AppError> {
+    let dry_run = payload.dry_run.unwrap_or(true);
+    let (checked, mismatches) =
+        rebuild_running_states(&state.db, payload.card_id.as_deref(), dry_run)
+            .await
+            .map_err(internal_error)?;
+    if payload.card_id.is_some() && checked == 0 {
+        return Err(AppError(
+            StatusCode::NOT_FOUND,
+            "Card not found".to_string(),
+        ));
+    }
+    if !dry_run {
+        for mismatch in &mismatches {
+            invalidate_card_cache(&state, &mismatch.user_id).await;
+        }
+    }
+    Ok(Json(RebuildRunningStateResponse {
+        dry_run,
+        checked,
+        mismatches,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAX: Money = Money::from_minor(1_000_000);
+
+    fn money(minor: i64) -> Money {
+        Money::from_minor(minor)
+    }
+
+    fn state(total: i64, delta: i64) -> RunningState {
+        RunningState {
+            last_total_due: money(total),
+            last_delta: money(delta),
+        }
+    }
+
+    fn event(event_type: EventType, amount: i64, total_due_input: i64) -> StoredEvent {
+        StoredEvent {
+            transaction_id: String::new(),
+            timestamp: String::new(),
+            event_type,
+            amount: (event_type != EventType::Due).then(|| money(amount)),
+            total_due_input: money(total_due_input),
+        }
+    }
+
+    fn status(result: Result<impl Sized, AppError>) -> StatusCode {
+        match result {
+            Ok(_) => StatusCode::OK,
+            Err(AppError(status, _)) => status,
+        }
+    }
+
+    #[test]
+    fn each_event_type_moves_the_total_and_delta() {
+        let before = state(10_000, 2_500);
+        assert_eq!(
+            apply(before, EventType::Due, money(12_000)),
+            state(12_000, 2_000)
+        );
+        assert_eq!(
+            apply(before, EventType::Due, money(10_000)),
+            state(10_000, 0)
+        );
+        assert_eq!(
+            apply(before, EventType::Payment, money(4_000)),
+            state(6_000, 0)
+        );
+        assert_eq!(
+            apply(before, EventType::Refund, money(1_500)),
+            state(8_500, -1_500)
+        );
+        assert_eq!(
+            apply(before, EventType::Fee, money(300)),
+            state(10_300, 300)
+        );
+        assert_eq!(
+            apply(before, EventType::Purchase, money(4_999)),
+            state(14_999, 4_999)
+        );
+        assert_eq!(
+            apply(before, EventType::Adjustment, money(-700)),
+            state(9_300, 0)
+        );
+        assert_eq!(
+            apply(before, EventType::Adjustment, money(700)),
+            state(10_700, 0)
+        );
+    }
+
+    #[test]
+    fn amounts_with_the_wrong_sign_are_bad_requests() {
+        let current = state(10_000, 0);
+        for (event_type, amount) in [
+            (EventType::Due, -1),
+            (EventType::Due, MAX.minor() + 1),
+            (EventType::Payment, 0),
+            (EventType::Refund, -100),
+            (EventType::Fee, 0),
+            (EventType::Purchase, -1),
+            (EventType::Adjustment, 0),
+        ] {
+            assert_eq!(
+                status(validate(current, event_type, money(amount), MAX)),
+                StatusCode::BAD_REQUEST,
+                "{} {}",
+                event_type.as_str(),
+                amount
+            );
+        }
+    }
+
+    #[test]
+    fn totals_out_of_range_are_unprocessable() {
+        let current = state(10_000, 0);
+        for (event_type, amount) in [
+            // A lower snapshot has to be sent as a payment.
+            (EventType::Due, 9_999),
+            (EventType::Payment, 10_001),
+            (EventType::Refund, 10_001),
+            (EventType::Adjustment, -10_001),
+            (EventType::Fee, MAX.minor()),
+            (EventType::Purchase, MAX.minor() - 9_999),
+            (EventType::Adjustment, MAX.minor()),
+        ] {
+            assert_eq!(
+                status(validate(current, event_type, money(amount), MAX)),
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "{} {}",
+                event_type.as_str(),
+                amount
+            );
+        }
+    }
+
+    #[test]
+    fn totals_within_range_are_accepted() {
+        let current = state(10_000, 0);
+        for (event_type, amount) in [
+            (EventType::Due, 10_000),
+            (EventType::Due, MAX.minor()),
+            (EventType::Payment, 10_000),
+            (EventType::Refund, 1),
+            (EventType::Adjustment, -10_000),
+            (EventType::Purchase, MAX.minor() - 10_000),
+        ] {
+            assert_eq!(
+                status(validate(current, event_type, money(amount), MAX)),
+                StatusCode::OK,
+                "{} {}",
+                event_type.as_str(),
+                amount
+            );
+        }
+    }
+
+    #[test]
+    fn a_lower_snapshot_sent_as_a_payment_becomes_one() {
+        let current = state(10_000, 0);
+        let resolved = resolve_input(current, EventType::Due, Some(money(7_500)), None, true);
+        assert!(matches!(resolved, Ok((EventType::Payment, amount)) if amount == money(2_500)));
+
+        for amount_due in [10_000, 12_000, -1] {
+            assert_eq!(
+                status(resolve_input(
+                    current,
+                    EventType::Due,
+                    Some(money(amount_due)),
+                    None,
+                    true
+                )),
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "{}",
+                amount_due
+            );
+        }
+    }
+
+    #[test]
+    fn inputs_must_match_the_event_type() {
+        let current = state(10_000, 0);
+        assert!(matches!(
+            resolve_input(current, EventType::Due, Some(money(12_000)), None, false),
+            Ok((EventType::Due, amount)) if amount == money(12_000)
+        ));
+        assert!(matches!(
+            resolve_input(current, EventType::Fee, None, Some(money(500)), false),
+            Ok((EventType::Fee, amount)) if amount == money(500)
+        ));
+
+        for (event_type, amount_due, amount, payment) in [
+            (EventType::Due, None, None, false),
+            (EventType::Due, None, Some(100), false),
+            (EventType::Due, Some(100), Some(100), false),
+            (EventType::Refund, None, Some(100), true),
+            (EventType::Refund, Some(100), None, false),
+            (EventType::Purchase, None, None, false),
+        ] {
+            assert_eq!(
+                status(resolve_input(
+                    current,
+                    event_type,
+                    amount_due.map(money),
+                    amount.map(money),
+                    payment
+                )),
+                StatusCode::BAD_REQUEST,
+                "{} {:?} {:?} {}",
+                event_type.as_str(),
+                amount_due,
+                amount,
+                payment
+            );
+        }
+    }
+
+    #[test]
+    fn replay_applies_a_mixed_sequence_in_order() {
+        let events = [
+            event(EventType::Due, 0, 10_000),
+            event(EventType::Purchase, 2_000, 12_000),
+            event(EventType::Payment, 7_000, 5_000),
+            event(EventType::Refund, 500, 4_500),
+            event(EventType::Fee, 250, 4_750),
+            event(EventType::Due, 0, 6_000),
+            event(EventType::Adjustment, -1_000, 5_000),
+        ];
+        let (state_after, totals) = replay(&events);
+        assert_eq!(
+            totals,
+            [10_000, 12_000, 5_000, 4_500, 4_750, 6_000, 5_000].map(money)
+        );
+        assert_eq!(state_after, state(5_000, 0));
+
+        // The delta is the last event's own.
+        let (state_after, _) = replay(&events[..6]);
+        assert_eq!(state_after, state(6_000, 1_250));
+        let (state_after, _) = replay(&events[..4]);
+        assert_eq!(state_after, state(4_500, -500));
+
+        assert_eq!(replay(&[]), (RunningState::default(), Vec::new()));
+    }
+}
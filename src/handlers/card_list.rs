@@ -0,0 +1,284 @@
+use std::cmp::Ordering;
+
+use axum::http::StatusCode;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use serde::{Deserialize, Serialize};
+use time::{
+    format_description::well_known::Rfc3339, macros::format_description, Date, OffsetDateTime,
+    UtcOffset,
+};
+
+use crate::{
+    handlers::common::AppError,
+    models::{CardSort, GetAllCardsQuery, SortOrder},
+    money::Money,
+};
+
+pub const MAX_PAGE_SIZE: u32 = 200;
+
+/// The value a card is ordered by under the requested sort.
+#[derive(Serialize, Deserialize, Clone)]
+pub enum SortKey {
+    Position { pinned: bool, position: i64 },
+    Amount(Money),
+    Text(String),
+}
+
+/// Where the previous page stopped. Carries the sort it was issued for so a
+/// cursor can't be replayed against a different ordering.
+#[derive(Serialize, Deserialize)]
+struct Cursor {
+    sort: CardSort,
+    order: SortOrder,
+    key: SortKey,
+    card_id: String,
+}
+
+pub fn sort_key(
+    sort: CardSort,
+    pinned: bool,
+    position: i64,
+    last_total_due: Money,
+    last_delta: Money,
+    card_name: &str,
+    updated_at: &str,
+) -> SortKey {
+    match sort {
+        CardSort::Position => SortKey::Position { pinned, position },
+        CardSort::Due => SortKey::Amount(last_total_due),
+        CardSort::Delta => SortKey::Amount(last_delta),
+        // ASCII only, like SQLite's `lower()`, so cursors match the list query.
+        CardSort::Name => SortKey::Text(card_name.to_ascii_lowercase()),
+        CardSort::Updated => SortKey::Text(updated_at.to_string()),
+    }
+}
+
+fn compare_keys(a: &SortKey, b: &SortKey) -> Ordering {
+    match (a, b) {
+        (
+            SortKey::Position {
+                pinned: a_pinned,
+                position: a_position,
+            },
+            SortKey::Position {
+                pinned: b_pinned,
+                position: b_position,
+            },
+        ) => b_pinned.cmp(a_pinned).then(a_position.cmp(b_position)),
+        (SortKey::Amount(a), SortKey::Amount(b)) => a.cmp(b),
+        (SortKey::Text(a), SortKey::Text(b)) => a.cmp(b),
+        _ => Ordering::Equal,
+    }
+}
+
+/// Total order used for both sorting and cursors: the sort key, then card id
+/// so equal keys still page deterministically, all in the requested direction.
+/// Matches the list query's `ORDER BY`.
+fn compare(order: SortOrder, a: (&SortKey, &str), b: (&SortKey, &str)) -> Ordering {
+    let ordering = compare_keys(a.0, b.0).then_with(|| a.1.cmp(b.1));
+    match order {
+        SortOrder::Asc => ordering,
+        SortOrder::Desc => ordering.reverse(),
+    }
+}
+
+impl SortKey {
+    /// The key as the list query's three ordering columns: a number, a position
+    /// and a text, each zero or empty when the sort doesn't use it.
+    fn columns(self) -> (i64, i64, String) {
+        match self {
+            SortKey::Position { pinned, position } => (i64::from(!pinned), position, String::new()),
+            SortKey::Amount(amount) => (amount.minor(), 0, String::new()),
+            SortKey::Text(text) => (0, 0, text),
+        }
+    }
+}
+
+/// Where the list query resumes: the ordering columns and card id of the last
+/// card on the previous page.
+pub struct After {
+    pub number: i64,
+    pub position: i64,
+    pub text: String,
+    pub card_id: String,
+}
+
+fn decode_query_cursor(query: &GetAllCardsQuery) -> Result<Option<Cursor>, AppError> {
+    let Some(cursor) = &query.cursor else {
+        return Ok(None);
+    };
+    let cursor = decode_cursor(cursor)?;
+    if cursor.sort != query.sort || cursor.order != query.order {
+        return Err(bad_request(
+            "cursor was issued for a different sort; start again without it",
+        ));
+    }
+    Ok(Some(cursor))
+}
+
+/// The position the query's cursor points past, if it has one.
+pub fn after(query: &GetAllCardsQuery) -> Result<Option<After>, AppError> {
+    Ok(decode_query_cursor(query)?.map(|cursor| {
+        let (number, position, text) = cursor.key.columns();
+        After {
+            number,
+            position,
+            text,
+            card_id: cursor.card_id,
+        }
+    }))
+}
+
+/// How many rows the list query should fetch: one more than the page, so it
+/// can tell whether there is a next one. `-1` is SQLite for no limit.
+pub fn fetch_limit(query: &GetAllCardsQuery) -> i64 {
+    query.limit.map_or(-1, |limit| i64::from(limit) + 1)
+}
+
+fn bad_request(message: &str) -> AppError {
+    AppError(StatusCode::BAD_REQUEST, message.to_string())
+}
+
+pub fn validate_query(query: &GetAllCardsQuery) -> Result<(), AppError> {
+    if query
+        .limit
+        .is_some_and(|limit| !(1..=MAX_PAGE_SIZE).contains(&limit))
+    {
+        return Err(bad_request(&format!(
+            "limit must be between 1 and {}",
+            MAX_PAGE_SIZE
+        )));
+    }
+    if let (Some(min_due), Some(max_due)) = (query.min_due, query.max_due)
+        && min_due > max_due
+    {
+        return Err(bad_request("min_due must not be greater than max_due"));
+    }
+    Ok(())
+}
+
+/// `LIKE` pattern matching `q` anywhere, with the wildcards in `q` escaped (use `ESCAPE '\'`).
+pub fn like_pattern(q: &str) -> String {
+    let escaped = q
+        .trim()
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    format!("%{}%", escaped)
+}
+
+/// Normalizes `updated_since` to SQLite's `YYYY-MM-DD HH:MM:SS` (UTC) so it
+/// compares correctly against `CURRENT_TIMESTAMP` columns.
+pub fn parse_updated_since(value: &str) -> Result<String, AppError> {
+    parse_instant("updated_since", value)
+}
+
+/// Parses an RFC 3339 timestamp or a date (taken as midnight UTC) into the
+/// `YYYY-MM-DD HH:MM:SS` UTC form SQLite's `CURRENT_TIMESTAMP` uses, so it
+/// compares correctly with stored timestamps.
+pub fn parse_instant(field: &str, value: &str) -> Result<String, AppError> {
+    let sqlite_format = format_description!("[year]-[month]-[day] [hour]:[minute]:[second]");
+    let instant = OffsetDateTime::parse(value, &Rfc3339)
+        .map(|dt| dt.to_offset(UtcOffset::UTC))
+        .or_else(|_| {
+            Date::parse(value, format_description!("[year]-[month]-[day]"))
+                .map(|date| date.midnight().assume_utc())
+        })
+        .map_err(|_| {
+            bad_request(&format!(
+                "{} must be an RFC 3339 timestamp or YYYY-MM-DD",
+                field
+            ))
+        })?;
+    instant
+        .format(sqlite_format)
+        .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+}
+
+/// Cuts a page from `cards`, which are already in order and start after the
+/// cursor. Returns the page and the cursor for the next one, if any.
+pub fn page<T>(
+    mut cards: Vec<(SortKey, String, T)>,
+    query: &GetAllCardsQuery,
+) -> (Vec<T>, Option<String>) {
+    let mut next_cursor = None;
+    if let Some(limit) = query.limit.map(|limit| limit as usize)
+        && cards.len() > limit
+    {
+        cards.truncate(limit);
+        next_cursor = cards.last().map(|(key, card_id, _)| {
+            encode_cursor(&Cursor {
+                sort: query.sort,
+                order: query.order,
+                key: key.clone(),
+                card_id: card_id.clone(),
+            })
+        });
+    }
+
+    (
+        cards.into_iter().map(|(_, _, card)| card).collect(),
+        next_cursor,
+    )
+}
+
+/// Sorts the cards in memory, skips everything up to the cursor and cuts the
+/// page. For point-in-time lists, whose balances only exist after the replay.
+pub fn paginate<T>(
+    mut cards: Vec<(SortKey, String, T)>,
+    query: &GetAllCardsQuery,
+) -> Result<(Vec<T>, Option<String>), AppError> {
+    let order = query.order;
+    cards.sort_by(|a, b| compare(order, (&a.0, &a.1), (&b.0, &b.1)));
+
+    if let Some(cursor) = decode_query_cursor(query)? {
+        cards.retain(|(key, card_id, _)| {
+            compare(order, (key, card_id), (&cursor.key, &cursor.card_id)) == Ordering::Greater
+        });
+    }
+
+    Ok(page(cards, query))
+}
+
+fn encode_cursor(cursor: &Cursor) -> String {
+    URL_SAFE_NO_PAD.encode(serde_json::to_vec(cursor).expect("cursor serializes"))
+}
+
+fn decode_cursor(cursor: &str) -> Result<Cursor, AppError> {
+    URL_SAFE_NO_PAD
+        .decode(cursor)
+        .ok()
+        .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+        .ok_or_else(|| bad_request("cursor is not valid"))
+}
+
+/// Identifies the cached variant of a list query, or `None` if it shouldn't be
+/// cached. Only low-cardinality parameters are cached; free-text search, amount
+/// ranges, dates and cursors would each mint a new key per request, and
+/// point-in-time (`as_of`) lists are replayed rather than read live.
+pub fn cache_variant(query: &GetAllCardsQuery) -> Option<String> {
+    if query.q.is_some()
+        || query.min_due.is_some()
+        || query.max_due.is_some()
+        || query.updated_since.is_some()
+        || query.cursor.is_some()
+        || query.as_of.is_some()
+    {
+        return None;
+    }
+    Some(format!(
+        "archived={}&tag={}&status={}&sort={}&order={}&limit={}",
+        u8::from(query.include_archived),
+        query.tag.as_deref().unwrap_or_default(),
+        query
+            .status
+            .map(|status| status.as_str())
+            .unwrap_or_default(),
+        query.sort.as_str(),
+        query.order.as_str(),
+        query
+            .limit
+            .map(|limit| limit.to_string())
+            .unwrap_or_default()
+    ))
+}
@@ -1,5 +1,6 @@
-pub fn pack(color: (u8, u8, u8)) -> i32 {
-    ((color.0 as i32) << 16) | ((color.1 as i32) << 8) | (color.2 as i32)
+/// Packs an RGB color into the integer stored in the database.
+pub fn pack(color: (u8, u8, u8)) -> i64 {
+    ((color.0 as i64) << 16) | ((color.1 as i64) << 8) | (color.2 as i64)
 }
 
 pub fn unpack(color: i64) -> (u8, u8, u8) {
@@ -9,3 +10,164 @@ pub fn unpack(color: i64) -> (u8, u8, u8) {
         (color & 0xFF) as u8,
     )
 }
+
+pub fn to_hex(color: (u8, u8, u8)) -> String {
+    format!("#{:02x}{:02x}{:02x}", color.0, color.1, color.2)
+}
+
+pub const WHITE: (u8, u8, u8) = (0xFF, 0xFF, 0xFF);
+pub const BLACK: (u8, u8, u8) = (0x00, 0x00, 0x00);
+
+/// An RGB color with optional transparency; `alpha` is `None` when opaque.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Color {
+    pub rgb: (u8, u8, u8),
+    pub alpha: Option<u8>,
+}
+
+impl Color {
+    pub fn opaque(rgb: (u8, u8, u8)) -> Self {
+        Color { rgb, alpha: None }
+    }
+
+    /// Parses `#rrggbb` or `#rrggbbaa`.
+    pub fn parse_hex(value: &str) -> Option<Self> {
+        let hex = value.trim().strip_prefix('#')?;
+        if !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return None;
+        }
+        let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+        let (rgb, alpha) = match hex.len() {
+            6 => ((channel(0)?, channel(2)?, channel(4)?), None),
+            8 => ((channel(0)?, channel(2)?, channel(4)?), Some(channel(6)?)),
+            _ => return None,
+        };
+        Some(Color {
+            rgb,
+            alpha: alpha.filter(|alpha| *alpha != 0xFF),
+        })
+    }
+
+    /// `#rrggbb`, or `#rrggbbaa` when translucent.
+    pub fn to_hex(self) -> String {
+        match self.alpha {
+            Some(alpha) => format!("{}{:02x}", to_hex(self.rgb), alpha),
+            None => to_hex(self.rgb),
+        }
+    }
+
+    /// What the color looks like drawn over `backdrop`.
+    pub fn over(self, backdrop: (u8, u8, u8)) -> (u8, u8, u8) {
+        let Some(alpha) = self.alpha else {
+            return self.rgb;
+        };
+        let alpha = alpha as f64 / 255.0;
+        let blend = |fg: u8, bg: u8| (fg as f64 * alpha + bg as f64 * (1.0 - alpha)).round() as u8;
+        (
+            blend(self.rgb.0, backdrop.0),
+            blend(self.rgb.1, backdrop.1),
+            blend(self.rgb.2, backdrop.2),
+        )
+    }
+}
+
+/// WCAG 2.x relative luminance, 0 for black to 1 for white.
+pub fn relative_luminance(color: (u8, u8, u8)) -> f64 {
+    let linear = |channel: u8| {
+        let c = channel as f64 / 255.0;
+        if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    0.2126 * linear(color.0) + 0.7152 * linear(color.1) + 0.0722 * linear(color.2)
+}
+
+/// WCAG contrast ratio between two colors, from 1 (none) to 21 (black on white).
+pub fn contrast_ratio(a: (u8, u8, u8), b: (u8, u8, u8)) -> f64 {
+    let (a, b) = (relative_luminance(a), relative_luminance(b));
+    (a.max(b) + 0.05) / (a.min(b) + 0.05)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 0.01
+    }
+
+    #[test]
+    fn packs_and_unpacks() {
+        let color = (0x12, 0xAB, 0xEF);
+        assert_eq!(pack(color), 0x12ABEF);
+        assert_eq!(unpack(pack(color)), color);
+        assert_eq!(to_hex(color), "#12abef");
+    }
+
+    #[test]
+    fn parses_hex_colors() {
+        assert!(Color::parse_hex("#1a2B3c") == Some(Color::opaque((0x1A, 0x2B, 0x3C))));
+        assert!(
+            Color::parse_hex(" #1a2b3c80 ")
+                == Some(Color {
+                    rgb: (0x1A, 0x2B, 0x3C),
+                    alpha: Some(0x80),
+                })
+        );
+        // Fully opaque alpha is the same as none.
+        assert!(Color::parse_hex("#1a2b3cff") == Some(Color::opaque((0x1A, 0x2B, 0x3C))));
+        assert_eq!(
+            Color::parse_hex("#1a2b3c80").map(Color::to_hex).as_deref(),
+            Some("#1a2b3c80")
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_hex() {
+        for value in [
+            "#abc",
+            "#GGGGGG",
+            "1a2b3c",
+            "",
+            "#",
+            "#1a2b3",
+            "#1a2b3c8",
+            "#1a2b3c8080",
+            "#1a2b3é",
+        ] {
+            assert!(Color::parse_hex(value).is_none(), "{}", value);
+        }
+    }
+
+    #[test]
+    fn translucent_colors_blend_over_the_backdrop() {
+        let half_red = Color {
+            rgb: (0xFF, 0x00, 0x00),
+            alpha: Some(0x80),
+        };
+        assert_eq!(half_red.over(WHITE), (0xFF, 0x7F, 0x7F));
+        assert_eq!(half_red.over(BLACK), (0x80, 0x00, 0x00));
+        assert_eq!(Color::opaque((1, 2, 3)).over(WHITE), (1, 2, 3));
+    }
+
+    #[test]
+    fn luminance_spans_black_to_white() {
+        assert_eq!(relative_luminance(BLACK), 0.0);
+        assert!(close(relative_luminance(WHITE), 1.0));
+        // Green weighs most, blue least.
+        assert!(close(relative_luminance((0, 0xFF, 0)), 0.7152));
+        assert!(close(relative_luminance((0, 0, 0xFF)), 0.0722));
+    }
+
+    #[test]
+    fn contrast_ratios_match_wcag() {
+        assert!(close(contrast_ratio(BLACK, WHITE), 21.0));
+        assert!(close(contrast_ratio(WHITE, BLACK), 21.0));
+        assert_eq!(contrast_ratio((0x77, 0x77, 0x77), (0x77, 0x77, 0x77)), 1.0);
+        // #767676 is the lightest grey that passes AA (4.5:1) on white.
+        assert!(close(contrast_ratio((0x76, 0x76, 0x76), WHITE), 4.54));
+        assert!(contrast_ratio((0x77, 0x77, 0x77), WHITE) < 4.5);
+    }
+}
@@ -84,7 +84,7 @@ fn verify_user(database_password: String, user_input_password: String) -> Result
     Ok(())
 }
 
-fn get_paseto_token(
+pub(crate) fn get_paseto_token(
     user_id: &str,
     user_role: String,
     key: &Arc<PasetoSymmetricKey<V4, Local>>,
@@ -0,0 +1,305 @@
+use axum::{extract::State, http::StatusCode, Extension, Json};
+use sqlx::SqlitePool;
+use time::{macros::format_description, Date, OffsetDateTime};
+use tracing::error;
+
+use crate::{
+    handlers::common::AppError,
+    models::{
+        AppState, CurrencySubtotal, DeleteFxRatePayload, FxRate, HomeCurrencyTotalResponse,
+        SetFxRatePayload,
+    },
+    money::Money,
+};
+
+/// Upper-cases and checks an ISO 4217 style code (three ASCII letters).
+pub fn parse_currency_code(code: &str) -> Option<String> {
+    let code = code.trim();
+    (code.len() == 3 && code.bytes().all(|b| b.is_ascii_alphabetic()))
+        .then(|| code.to_ascii_uppercase())
+}
+
+/// ISO 4217 currencies whose minor unit isn't a hundredth (no decimals, or
+/// three or four). `Money` keeps every amount in hundredths, so these can't be
+/// used until amounts carry their currency's exponent.
+const NON_CENT_CURRENCIES: [&str; 26] = [
+    "BHD", "BIF", "CLF", "CLP", "DJF", "GNF", "IQD", "ISK", "JOD", "JPY", "KMF", "KRW", "KWD",
+    "LYD", "OMR", "PYG", "RWF", "TND", "UGX", "UYI", "UYW", "VND", "VUV", "XAF", "XOF", "XPF",
+];
+
+/// Whether amounts in `code` (already upper-cased) have two decimal places.
+pub fn has_cents(code: &str) -> bool {
+    !NON_CENT_CURRENCIES.contains(&code)
+}
+
+/// Parses a currency code for use with `Money`: a three-letter code whose
+/// amounts have two decimal places.
+pub fn currency_code_or_400(field: &str, code: &str) -> Result<String, AppError> {
+    let code = parse_currency_code(code).ok_or_else(|| {
+        AppError(
+            StatusCode::BAD_REQUEST,
+            format!("{} must be a three-letter ISO 4217 code", field),
+        )
+    })?;
+    if !has_cents(&code) {
+        return Err(AppError(
+            StatusCode::BAD_REQUEST,
+            format!(
+                "{} {} is not supported; only currencies with two decimal places are",
+                field, code
+            ),
+        ));
+    }
+    Ok(code)
+}
+
+/// Checks an `as_of` date, defaulting to today (UTC).
+pub fn parse_as_of(as_of: Option<&str>) -> Option<String> {
+    match as_of {
+        Some(as_of) => Date::parse(as_of, format_description!("[year]-[month]-[day]"))
+            .ok()
+            .map(|date| date.to_string()),
+        None => Some(OffsetDateTime::now_utc().date().to_string()),
+    }
+}
+
+pub async fn list_rates(db: &SqlitePool) -> Result<Vec<FxRate>, sqlx::Error> {
+    sqlx::query_as!(
+        FxRate,
+        r#"SELECT base_currency, quote_currency, rate,
+                as_of as "as_of!: String", updated_at as "updated_at!: String"
+         FROM fx_rates
+         ORDER BY base_currency, quote_currency"#
+    )
+    .fetch_all(db)
+    .await
+}
+
+pub async fn upsert_rate(
+    db: &SqlitePool,
+    base_currency: &str,
+    quote_currency: &str,
+    rate: f64,
+    as_of: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "INSERT INTO fx_rates (base_currency, quote_currency, rate, as_of) VALUES (?, ?, ?, ?)
+         ON CONFLICT (base_currency, quote_currency)
+         DO UPDATE SET rate = excluded.rate, as_of = excluded.as_of, updated_at = CURRENT_TIMESTAMP",
+        base_currency,
+        quote_currency,
+        rate,
+        as_of
+    )
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+pub async fn delete_rate(
+    db: &SqlitePool,
+    base_currency: &str,
+    quote_currency: &str,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        "DELETE FROM fx_rates WHERE base_currency = ? AND quote_currency = ?",
+        base_currency,
+        quote_currency
+    )
+    .execute(db)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Units of `to` per unit of `from` and the date the rate is valid for. Uses the
+/// stored pair in either direction; currencies are never chained through a third.
+pub async fn conversion_rate(
+    db: &SqlitePool,
+    from: &str,
+    to: &str,
+) -> Result<Option<(f64, String)>, sqlx::Error> {
+    if from == to {
+        return Ok(Some((1.0, OffsetDateTime::now_utc().date().to_string())));
+    }
+
+    let rate = sqlx::query!(
+        r#"SELECT rate, as_of as "as_of!: String", base_currency = ? as "direct!: bool"
+         FROM fx_rates
+         WHERE (base_currency = ? AND quote_currency = ?)
+            OR (base_currency = ? AND quote_currency = ?)
+         ORDER BY base_currency = ? DESC
+         LIMIT 1"#,
+        from,
+        from,
+        to,
+        to,
+        from,
+        from
+    )
+    .fetch_optional(db)
+    .await?;
+
+    Ok(rate.map(|rate| {
+        let value = if rate.direct {
+            rate.rate
+        } else {
+            1.0 / rate.rate
+        };
+        (value, rate.as_of)
+    }))
+}
+
+pub async fn get_rates(State(state): State<AppState>) -> Result<Json<Vec<FxRate>>, AppError> {
+    let rates = list_rates(&state.db)
+        .await
+        .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    Ok(Json(rates))
+}
+
+pub async fn set_rate(
+    State(state): State<AppState>,
+    Json(payload): Json<SetFxRatePayload>,
+) -> Result<Json<Vec<FxRate>>, AppError> {
+    let base_currency = currency_code_or_400("base_currency", &payload.base_currency)?;
+    let quote_currency = currency_code_or_400("quote_currency", &payload.quote_currency)?;
+    if base_currency == quote_currency {
+        return Err(AppError(
+            StatusCode::BAD_REQUEST,
+            "base_currency and quote_currency must differ".to_string(),
+        ));
+    }
+    if !payload.rate.is_finite() || payload.rate <= 0.0 {
+        return Err(AppError(
+            StatusCode::BAD_REQUEST,
+            "rate must be a positive number".to_string(),
+        ));
+    }
+    let as_of = parse_as_of(payload.as_of.as_deref()).ok_or_else(|| {
+        AppError(
+            StatusCode::BAD_REQUEST,
+            "as_of must be a date in YYYY-MM-DD format".to_string(),
+        )
+    })?;
+
+    upsert_rate(
+        &state.db,
+        &base_currency,
+        &quote_currency,
+        payload.rate,
+        &as_of,
+    )
+    .await
+    .map_err(|e| {
+        error!("Error saving exchange rate {}", e);
+        AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+    })?;
+
+    get_rates(State(state)).await
+}
+
+pub async fn remove_rate(
+    State(state): State<AppState>,
+    Json(payload): Json<DeleteFxRatePayload>,
+) -> Result<Json<Vec<FxRate>>, AppError> {
+    let base_currency = currency_code_or_400("base_currency", &payload.base_currency)?;
+    let quote_currency = currency_code_or_400("quote_currency", &payload.quote_currency)?;
+
+    let deleted = delete_rate(&state.db, &base_currency, &quote_currency)
+        .await
+        .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    if !deleted {
+        return Err(AppError(
+            StatusCode::NOT_FOUND,
+            "Exchange rate not found".to_string(),
+        ));
+    }
+
+    get_rates(State(state)).await
+}
+
+/// Current total due across the user's active cards, converted into their home
+/// currency. Currencies without a rate on file are reported but left out of the total.
+/// Only the newest card in a replacement chain is summed, so an inherited balance
+/// isn't counted twice.
+pub async fn get_home_currency_total(
+    State(state): State<AppState>,
+    Extension((user_id, _role)): Extension<(String, String)>,
+) -> Result<Json<HomeCurrencyTotalResponse>, AppError> {
+    let home_currency =
+        sqlx::query_scalar!("SELECT home_currency FROM users WHERE user_id = ?", user_id)
+            .fetch_optional(&state.db)
+            .await
+            .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+            .ok_or_else(|| AppError(StatusCode::NOT_FOUND, "User not found".to_string()))?;
+
+    let totals = sqlx::query!(
+        r#"SELECT c.currency, COALESCE(SUM(crs.last_total_due), 0) as "total_due!: Money"
+         FROM cards c
+         LEFT JOIN card_running_state crs ON c.card_id = crs.card_id
+         WHERE c.user_id = ? AND c.deleted_at IS NULL AND c.archived_at IS NULL
+           AND c.replaced_by IS NULL
+         GROUP BY c.currency
+         ORDER BY c.currency"#,
+        user_id
+    )
+    .fetch_all(&state.db)
+    .await
+    .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let mut currencies = Vec::with_capacity(totals.len());
+    let mut total_due = Money::ZERO;
+    let mut complete = true;
+    for subtotal in totals {
+        let rate = conversion_rate(&state.db, &subtotal.currency, &home_currency)
+            .await
+            .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        let converted = rate
+            .as_ref()
+            .map(|(rate, _)| subtotal.total_due.scale(*rate));
+        match converted {
+            Some(converted) => total_due = total_due + converted,
+            None => complete = false,
+        }
+        currencies.push(CurrencySubtotal {
+            currency: subtotal.currency,
+            total_due: subtotal.total_due,
+            rate: rate.as_ref().map(|(rate, _)| *rate),
+            rate_as_of: rate.map(|(_, as_of)| as_of),
+            converted_total_due: converted,
+        });
+    }
+
+    Ok(Json(HomeCurrencyTotalResponse {
+        home_currency,
+        total_due,
+        complete,
+        currencies,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_two_decimal_currencies_are_accepted() {
+        assert_eq!(
+            currency_code_or_400("currency", " usd ").ok().as_deref(),
+            Some("USD")
+        );
+        assert_eq!(
+            currency_code_or_400("currency", "eur").ok().as_deref(),
+            Some("EUR")
+        );
+        for code in ["JPY", "krw", "KWD", "CLF", "US", "US1", "USDT"] {
+            assert!(
+                matches!(
+                    currency_code_or_400("currency", code),
+                    Err(AppError(StatusCode::BAD_REQUEST, _))
+                ),
+                "{}",
+                code
+            );
+        }
+    }
+}
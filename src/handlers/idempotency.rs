@@ -0,0 +1,238 @@
+use axum::http::{HeaderMap, StatusCode};
+use redis::AsyncCommands;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use sqlx::{SqliteConnection, SqlitePool};
+use tracing::error;
+
+use crate::{handlers::common::AppError, models::AppState};
+
+const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+const MAX_KEY_LEN: usize = 255;
+/// How long a stored response is replayed for.
+const IDEMPOTENCY_TTL_SECONDS: u64 = 24 * 60 * 60;
+/// How long a reservation holds a key before another attempt may take it over,
+/// in case the request that reserved it never finished.
+const RESERVATION_LEASE_SECONDS: u64 = 60;
+
+/// The request a key was first used with, and the response it got.
+#[derive(Serialize, Deserialize)]
+struct StoredResponse {
+    request: String,
+    response: Option<String>,
+}
+
+/// A key held by the request now running. Complete it in the same database
+/// transaction as the request's own writes, or release it if the request fails.
+pub struct Reservation {
+    user_id: String,
+    key: String,
+    request: String,
+}
+
+pub enum Reserved<Res> {
+    /// The key is new (or expired) and now belongs to this request.
+    New(Reservation),
+    /// The key already finished with this request; here is its response.
+    Replay(Res),
+}
+
+fn redis_key(user_id: &str, key: &str) -> String {
+    format!("idempotency:{}:{}", user_id, key)
+}
+
+fn internal_error(e: impl ToString) -> AppError {
+    AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+}
+
+/// The request's `Idempotency-Key`, if it sent one.
+pub fn key_from_headers(headers: &HeaderMap) -> Result<Option<String>, AppError> {
+    let Some(value) = headers.get(IDEMPOTENCY_KEY_HEADER) else {
+        return Ok(None);
+    };
+    value
+        .to_str()
+        .ok()
+        .map(str::trim)
+        .filter(|key| !key.is_empty() && key.len() <= MAX_KEY_LEN)
+        .map(|key| Some(key.to_string()))
+        .ok_or_else(|| {
+            AppError(
+                StatusCode::BAD_REQUEST,
+                format!(
+                    "Idempotency-Key must be 1 to {} visible ASCII characters",
+                    MAX_KEY_LEN
+                ),
+            )
+        })
+}
+
+/// Claims `key` for `request` (the payload as re-serialized, so formatting and
+/// field order don't matter) before any work is done. A retry of a finished
+/// request gets its stored response back; a key used for a different request
+/// is 422, and one whose first request is still running is 409.
+pub async fn reserve<Req: Serialize, Res: DeserializeOwned>(
+    state: &AppState,
+    user_id: &str,
+    key: &str,
+    request: &Req,
+) -> Result<Reserved<Res>, AppError> {
+    let request = serde_json::to_string(request).map_err(internal_error)?;
+
+    if let Some(stored) = cached(state, user_id, key).await {
+        return replay(stored, &request);
+    }
+
+    let lease = format!("+{} seconds", RESERVATION_LEASE_SECONDS);
+    let reserved = sqlx::query!(
+        "INSERT INTO idempotency_keys (user_id, idempotency_key, request, expires_at)
+         VALUES (?, ?, ?, datetime('now', ?))
+         ON CONFLICT (user_id, idempotency_key) DO UPDATE
+         SET request = excluded.request,
+             response = NULL,
+             created_at = CURRENT_TIMESTAMP,
+             expires_at = excluded.expires_at
+         WHERE idempotency_keys.expires_at <= CURRENT_TIMESTAMP",
+        user_id,
+        key,
+        request,
+        lease
+    )
+    .execute(&state.db)
+    .await
+    .map_err(internal_error)?;
+    if reserved.rows_affected() == 1 {
+        return Ok(Reserved::New(Reservation {
+            user_id: user_id.to_string(),
+            key: key.to_string(),
+            request,
+        }));
+    }
+
+    let stored = sqlx::query_as!(
+        StoredResponse,
+        "SELECT request, response FROM idempotency_keys WHERE user_id = ? AND idempotency_key = ?",
+        user_id,
+        key
+    )
+    .fetch_one(&state.db)
+    .await
+    .map_err(internal_error)?;
+    replay(stored, &request)
+}
+
+fn replay<Res: DeserializeOwned>(
+    stored: StoredResponse,
+    request: &str,
+) -> Result<Reserved<Res>, AppError> {
+    if stored.request != request {
+        return Err(AppError(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "This Idempotency-Key was already used with a different request".to_string(),
+        ));
+    }
+    let Some(response) = stored.response else {
+        return Err(AppError(
+            StatusCode::CONFLICT,
+            "A request with this Idempotency-Key is still in progress; retry shortly".to_string(),
+        ));
+    };
+    serde_json::from_str(&response)
+        .map(Reserved::Replay)
+        .map_err(internal_error)
+}
+
+/// Stores the response for a reserved key. Runs inside the request's own
+/// transaction, so the key and the work it guards commit or roll back together.
+pub async fn complete<Res: Serialize>(
+    conn: &mut SqliteConnection,
+    reservation: &Reservation,
+    response: &Res,
+) -> Result<(), AppError> {
+    let response = serde_json::to_string(response).map_err(internal_error)?;
+    let ttl = format!("+{} seconds", IDEMPOTENCY_TTL_SECONDS);
+    let completed = sqlx::query!(
+        "UPDATE idempotency_keys
+         SET response = ?, expires_at = datetime('now', ?)
+         WHERE user_id = ? AND idempotency_key = ? AND request = ? AND response IS NULL",
+        response,
+        ttl,
+        reservation.user_id,
+        reservation.key,
+        reservation.request
+    )
+    .execute(conn)
+    .await
+    .map_err(internal_error)?;
+    // The lease ran out and another attempt took the key over.
+    if completed.rows_affected() == 0 {
+        return Err(AppError(
+            StatusCode::CONFLICT,
+            "A request with this Idempotency-Key is still in progress; retry shortly".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Frees a key whose request failed, so a retry can run it again.
+pub async fn release(state: &AppState, reservation: &Reservation) {
+    if let Err(e) = sqlx::query!(
+        "DELETE FROM idempotency_keys
+         WHERE user_id = ? AND idempotency_key = ? AND request = ? AND response IS NULL",
+        reservation.user_id,
+        reservation.key,
+        reservation.request
+    )
+    .execute(&state.db)
+    .await
+    {
+        error!("Error releasing idempotency key {}", e);
+    }
+}
+
+/// Copies a completed response to Redis, when configured, so retries are
+/// answered without touching SQLite. SQLite keeps the record either way.
+pub async fn cache<Res: Serialize>(state: &AppState, reservation: &Reservation, response: &Res) {
+    let Some(mut redis) = state.redis.clone() else {
+        return;
+    };
+    let stored = match serde_json::to_string(response).and_then(|response| {
+        serde_json::to_string(&StoredResponse {
+            request: reservation.request.clone(),
+            response: Some(response),
+        })
+    }) {
+        Ok(stored) => stored,
+        Err(e) => {
+            error!("Error serializing idempotent response {}", e);
+            return;
+        }
+    };
+    let written: redis::RedisResult<()> = redis
+        .set_ex(
+            redis_key(&reservation.user_id, &reservation.key),
+            stored,
+            IDEMPOTENCY_TTL_SECONDS,
+        )
+        .await;
+    if let Err(e) = written {
+        error!("Error caching idempotency key in Redis {}", e);
+    }
+}
+
+async fn cached(state: &AppState, user_id: &str, key: &str) -> Option<StoredResponse> {
+    let mut redis = state.redis.clone()?;
+    let value = redis
+        .get::<_, Option<String>>(redis_key(user_id, key))
+        .await
+        .ok()??;
+    serde_json::from_str(&value).ok()
+}
+
+/// Deletes expired keys, and reservations whose lease ran out, from SQLite;
+/// Redis expires its own.
+pub async fn purge_expired_keys(db: &SqlitePool) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query!("DELETE FROM idempotency_keys WHERE expires_at <= CURRENT_TIMESTAMP")
+        .execute(db)
+        .await?;
+    Ok(result.rows_affected())
+}
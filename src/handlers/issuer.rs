@@ -0,0 +1,524 @@
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    Json,
+};
+use nanoid::nanoid;
+use sqlx::{Sqlite, Transaction};
+use tracing::error;
+
+use crate::{
+    handlers::{
+        card::invalidate_card_cache,
+        color::{pack, unpack},
+        common::AppError,
+        theme::Theme,
+    },
+    models::{
+        AppState, CreateIssuerPayload, DeleteIssuerPayload, Issuer, IssuerSearchQuery,
+        UpdateIssuerPayload,
+    },
+};
+
+const DEFAULT_SEARCH_LIMIT: u32 = 10;
+const MAX_SEARCH_LIMIT: u32 = 50;
+const MAX_ISSUER_NAME_LEN: usize = 60;
+
+/// Normalized form used to match bank names against aliases: ASCII lower-case
+/// with spaces, dots and hyphens dropped, so "H.D.F.C Bank" and "hdfcbank"
+/// agree. The issuer catalog migration applies the same rule in SQL.
+pub fn alias_key(name: &str) -> String {
+    name.trim()
+        .to_ascii_lowercase()
+        .chars()
+        .filter(|c| !matches!(c, ' ' | '.' | '-'))
+        .collect()
+}
+
+pub struct IssuerMatch {
+    pub issuer_id: String,
+    pub primary_color: (u8, u8, u8),
+    pub secondary_color: (u8, u8, u8),
+}
+
+/// The catalog entry a free-text bank name resolves to, if any.
+pub async fn match_issuer<'e, E>(
+    executor: E,
+    card_bank: &str,
+) -> Result<Option<IssuerMatch>, sqlx::Error>
+where
+    E: sqlx::SqliteExecutor<'e>,
+{
+    let key = alias_key(card_bank);
+    let issuer = sqlx::query!(
+        r#"SELECT i.issuer_id as "issuer_id!", i.primary_color, i.secondary_color
+         FROM issuer_aliases a
+         JOIN issuers i ON i.issuer_id = a.issuer_id
+         WHERE a.alias_key = ?"#,
+        key
+    )
+    .fetch_optional(executor)
+    .await?;
+
+    Ok(issuer.map(|issuer| IssuerMatch {
+        issuer_id: issuer.issuer_id,
+        primary_color: unpack(issuer.primary_color),
+        secondary_color: unpack(issuer.secondary_color),
+    }))
+}
+
+fn bad_request(message: &str) -> AppError {
+    AppError(StatusCode::BAD_REQUEST, message.to_string())
+}
+
+fn issuer_not_found() -> AppError {
+    AppError(StatusCode::NOT_FOUND, "Issuer not found".to_string())
+}
+
+fn issuer_name(name: &str) -> Result<String, AppError> {
+    let name = name.trim();
+    if alias_key(name).is_empty() || name.chars().count() > MAX_ISSUER_NAME_LEN {
+        return Err(bad_request(&format!(
+            "Issuer name must be 1 to {} characters",
+            MAX_ISSUER_NAME_LEN
+        )));
+    }
+    Ok(name.to_string())
+}
+
+/// Logos are served from the web app's own assets, so only plain relative paths are accepted.
+fn validate_logo_path(logo_path: Option<&str>) -> Result<(), AppError> {
+    if logo_path.is_some_and(|path| {
+        path.is_empty()
+            || path.starts_with('/')
+            || path.contains("..")
+            || path.contains(':')
+            || path.contains('\\')
+    }) {
+        return Err(bad_request(
+            "logo_path must be a relative asset path such as issuers/hdfc.svg",
+        ));
+    }
+    Ok(())
+}
+
+/// `(contains, prefix)` LIKE patterns on alias keys for an autocomplete query.
+fn search_patterns(q: &str) -> Option<(String, String)> {
+    let key = alias_key(q)
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    (!key.is_empty()).then(|| (format!("%{}%", key), format!("{}%", key)))
+}
+
+fn parse_aliases(aliases: &str) -> Result<Vec<String>, AppError> {
+    serde_json::from_str(aliases).map_err(|e| {
+        error!("Error decoding issuer aliases {}", e);
+        AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+    })
+}
+
+/// Autocomplete over names and aliases. Issuers with an alias starting with
+/// `q` come before ones that merely contain it; without `q` the catalog is
+/// listed alphabetically.
+pub async fn get_issuers(
+    State(state): State<AppState>,
+    Query(query): Query<IssuerSearchQuery>,
+) -> Result<Json<Vec<Issuer>>, AppError> {
+    let limit = query.limit.unwrap_or(DEFAULT_SEARCH_LIMIT);
+    if !(1..=MAX_SEARCH_LIMIT).contains(&limit) {
+        return Err(bad_request(&format!(
+            "limit must be between 1 and {}",
+            MAX_SEARCH_LIMIT
+        )));
+    }
+    let (contains, prefix) = query.q.as_deref().and_then(search_patterns).unzip();
+
+    let rows = sqlx::query!(
+        r#"SELECT i.issuer_id as "issuer_id!", i.name, i.primary_color, i.secondary_color,
+                i.logo_path,
+                (SELECT json_group_array(alias)
+                 FROM (SELECT a.alias FROM issuer_aliases a
+                       WHERE a.issuer_id = i.issuer_id AND NOT a.is_name
+                       ORDER BY a.alias COLLATE NOCASE)) as "aliases!: String"
+         FROM issuers i
+         WHERE ? IS NULL OR EXISTS (
+             SELECT 1 FROM issuer_aliases a
+             WHERE a.issuer_id = i.issuer_id AND a.alias_key LIKE ? ESCAPE '\'
+         )
+         ORDER BY ? IS NOT NULL AND NOT EXISTS (
+                 SELECT 1 FROM issuer_aliases a
+                 WHERE a.issuer_id = i.issuer_id AND a.alias_key LIKE ? ESCAPE '\'
+             ),
+             i.name COLLATE NOCASE
+         LIMIT ?"#,
+        contains,
+        contains,
+        prefix,
+        prefix,
+        limit
+    )
+    .fetch_all(&state.db)
+    .await
+    .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let issuers = rows
+        .into_iter()
+        .map(|row| {
+            Ok(Issuer {
+                issuer_id: row.issuer_id,
+                name: row.name,
+                aliases: parse_aliases(&row.aliases)?,
+                primary_color: unpack(row.primary_color),
+                secondary_color: unpack(row.secondary_color),
+                logo_path: row.logo_path,
+            })
+        })
+        .collect::<Result<Vec<_>, AppError>>()?;
+
+    Ok(Json(issuers))
+}
+
+pub async fn create_issuer(
+    State(state): State<AppState>,
+    Json(payload): Json<CreateIssuerPayload>,
+) -> Result<Json<Issuer>, AppError> {
+    let name = issuer_name(&payload.name)?;
+    validate_logo_path(payload.logo_path.as_deref())?;
+    // New cards for this bank start out with these colors.
+    Theme::new(payload.primary_color, payload.secondary_color).check_readable()?;
+
+    let issuer_id = nanoid!();
+    let primary_color = pack(payload.primary_color);
+    let secondary_color = pack(payload.secondary_color);
+
+    let mut tx = begin(&state).await?;
+
+    sqlx::query!(
+        "INSERT INTO issuers (issuer_id, name, primary_color, secondary_color, logo_path) VALUES (?, ?, ?, ?, ?)",
+        issuer_id,
+        name,
+        primary_color,
+        secondary_color,
+        payload.logo_path
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| issuer_write_error(e, &name))?;
+
+    write_aliases(&mut tx, &issuer_id, &name, &payload.aliases).await?;
+    let affected_users = relink_cards(&mut tx, &issuer_id).await?;
+    let issuer = load_issuer(&mut tx, &issuer_id).await?;
+    commit(tx).await?;
+
+    for user_id in affected_users {
+        invalidate_card_cache(&state, &user_id).await;
+    }
+
+    Ok(Json(issuer))
+}
+
+pub async fn update_issuer(
+    State(state): State<AppState>,
+    Json(payload): Json<UpdateIssuerPayload>,
+) -> Result<Json<Issuer>, AppError> {
+    validate_logo_path(payload.logo_path.as_deref())?;
+
+    let mut tx = begin(&state).await?;
+
+    let current = sqlx::query!(
+        "SELECT name, primary_color, secondary_color, logo_path FROM issuers WHERE issuer_id = ?",
+        payload.issuer_id
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .ok_or_else(issuer_not_found)?;
+
+    let name = match &payload.name {
+        Some(name) => issuer_name(name)?,
+        None => current.name.clone(),
+    };
+    let primary_color = payload
+        .primary_color
+        .map(pack)
+        .unwrap_or(current.primary_color);
+    let secondary_color = payload
+        .secondary_color
+        .map(pack)
+        .unwrap_or(current.secondary_color);
+    if payload.primary_color.is_some() || payload.secondary_color.is_some() {
+        Theme::new(unpack(primary_color), unpack(secondary_color)).check_readable()?;
+    }
+    let logo_path = payload.logo_path.or(current.logo_path);
+
+    sqlx::query!(
+        "UPDATE issuers SET name = ?, primary_color = ?, secondary_color = ?, logo_path = ?, updated_at = CURRENT_TIMESTAMP WHERE issuer_id = ?",
+        name,
+        primary_color,
+        secondary_color,
+        logo_path,
+        payload.issuer_id
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| issuer_write_error(e, &name))?;
+
+    if payload.name.is_some() || payload.aliases.is_some() {
+        let aliases = match payload.aliases {
+            Some(aliases) => aliases,
+            None => sqlx::query_scalar!(
+                "SELECT alias FROM issuer_aliases WHERE issuer_id = ? AND NOT is_name",
+                payload.issuer_id
+            )
+            .fetch_all(&mut *tx)
+            .await
+            .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?,
+        };
+        write_aliases(&mut tx, &payload.issuer_id, &name, &aliases).await?;
+    }
+
+    // Users whose cards show this issuer before or after the change all need fresh lists.
+    let mut affected_users = linked_users(&mut tx, &payload.issuer_id).await?;
+    affected_users.extend(relink_cards(&mut tx, &payload.issuer_id).await?);
+    affected_users.sort();
+    affected_users.dedup();
+
+    let issuer = load_issuer(&mut tx, &payload.issuer_id).await?;
+    commit(tx).await?;
+
+    for user_id in affected_users {
+        invalidate_card_cache(&state, &user_id).await;
+    }
+
+    Ok(Json(issuer))
+}
+
+/// Removes a catalog entry. Linked cards keep their bank name and colors and
+/// just lose the link.
+pub async fn delete_issuer(
+    State(state): State<AppState>,
+    Json(payload): Json<DeleteIssuerPayload>,
+) -> Result<Json<bool>, AppError> {
+    let mut tx = begin(&state).await?;
+    let affected_users = linked_users(&mut tx, &payload.issuer_id).await?;
+
+    let result = sqlx::query!("DELETE FROM issuers WHERE issuer_id = ?", payload.issuer_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    if result.rows_affected() == 0 {
+        return Err(issuer_not_found());
+    }
+    commit(tx).await?;
+
+    for user_id in affected_users {
+        invalidate_card_cache(&state, &user_id).await;
+    }
+
+    Ok(Json(true))
+}
+
+async fn begin(state: &AppState) -> Result<Transaction<'static, Sqlite>, AppError> {
+    state.db.begin().await.map_err(|e| {
+        error!("Error starting transaction: {}", e);
+        AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+    })
+}
+
+async fn commit(tx: Transaction<'static, Sqlite>) -> Result<(), AppError> {
+    tx.commit()
+        .await
+        .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+}
+
+fn issuer_write_error(e: sqlx::Error, name: &str) -> AppError {
+    if e.as_database_error()
+        .is_some_and(|e| e.is_unique_violation())
+    {
+        return AppError(
+            StatusCode::CONFLICT,
+            format!("An issuer named {} already exists", name),
+        );
+    }
+    error!("Error saving issuer {}", e);
+    AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+}
+
+/// Replaces the issuer's alias rows with its name plus `aliases`. Spellings
+/// that normalize to the same key are stored once.
+async fn write_aliases(
+    tx: &mut Transaction<'static, Sqlite>,
+    issuer_id: &str,
+    name: &str,
+    aliases: &[String],
+) -> Result<(), AppError> {
+    sqlx::query!("DELETE FROM issuer_aliases WHERE issuer_id = ?", issuer_id)
+        .execute(&mut **tx)
+        .await
+        .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let mut seen = Vec::new();
+    let spellings =
+        std::iter::once((name, true)).chain(aliases.iter().map(|alias| (alias.trim(), false)));
+    for (alias, is_name) in spellings {
+        let key = alias_key(alias);
+        if key.is_empty() || seen.contains(&key) {
+            continue;
+        }
+        sqlx::query!(
+            "INSERT INTO issuer_aliases (alias_key, alias, issuer_id, is_name) VALUES (?, ?, ?, ?)",
+            key,
+            alias,
+            issuer_id,
+            is_name
+        )
+        .execute(&mut **tx)
+        .await
+        .map_err(|e| {
+            if e.as_database_error()
+                .is_some_and(|e| e.is_unique_violation())
+            {
+                AppError(
+                    StatusCode::CONFLICT,
+                    format!("{} is already an alias of another issuer", alias),
+                )
+            } else {
+                AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+            }
+        })?;
+        seen.push(key);
+    }
+    Ok(())
+}
+
+async fn linked_users(
+    tx: &mut Transaction<'static, Sqlite>,
+    issuer_id: &str,
+) -> Result<Vec<String>, AppError> {
+    sqlx::query_scalar!(
+        "SELECT DISTINCT user_id FROM cards WHERE issuer_id = ?",
+        issuer_id
+    )
+    .fetch_all(&mut **tx)
+    .await
+    .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+}
+
+/// Brings card links in line with the issuer's current aliases: unlinked cards
+/// whose bank now matches are linked, and linked cards that no longer match are
+/// released. Returns the users whose cards end up linked.
+async fn relink_cards(
+    tx: &mut Transaction<'static, Sqlite>,
+    issuer_id: &str,
+) -> Result<Vec<String>, AppError> {
+    sqlx::query!(
+        "UPDATE cards SET issuer_id = NULL
+         WHERE issuer_id = ?
+           AND replace(replace(replace(lower(trim(card_bank)), ' ', ''), '.', ''), '-', '')
+               NOT IN (SELECT alias_key FROM issuer_aliases WHERE issuer_id = ?)",
+        issuer_id,
+        issuer_id
+    )
+    .execute(&mut **tx)
+    .await
+    .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    sqlx::query!(
+        "UPDATE cards SET issuer_id = ?
+         WHERE issuer_id IS NULL
+           AND replace(replace(replace(lower(trim(card_bank)), ' ', ''), '.', ''), '-', '')
+               IN (SELECT alias_key FROM issuer_aliases WHERE issuer_id = ?)",
+        issuer_id,
+        issuer_id
+    )
+    .execute(&mut **tx)
+    .await
+    .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    linked_users(tx, issuer_id).await
+}
+
+async fn load_issuer(
+    tx: &mut Transaction<'static, Sqlite>,
+    issuer_id: &str,
+) -> Result<Issuer, AppError> {
+    let row = sqlx::query!(
+        r#"SELECT i.issuer_id as "issuer_id!", i.name, i.primary_color, i.secondary_color,
+                i.logo_path,
+                (SELECT json_group_array(alias)
+                 FROM (SELECT a.alias FROM issuer_aliases a
+                       WHERE a.issuer_id = i.issuer_id AND NOT a.is_name
+                       ORDER BY a.alias COLLATE NOCASE)) as "aliases!: String"
+         FROM issuers i
+         WHERE i.issuer_id = ?"#,
+        issuer_id
+    )
+    .fetch_optional(&mut **tx)
+    .await
+    .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .ok_or_else(issuer_not_found)?;
+
+    Ok(Issuer {
+        issuer_id: row.issuer_id,
+        name: row.name,
+        aliases: parse_aliases(&row.aliases)?,
+        primary_color: unpack(row.primary_color),
+        secondary_color: unpack(row.secondary_color),
+        logo_path: row.logo_path,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::TestApp;
+
+    #[test]
+    fn alias_keys_ignore_case_spacing_and_punctuation() {
+        for spelling in [
+            "HDFC Bank",
+            " hdfc bank ",
+            "H.D.F.C Bank",
+            "hdfc-bank",
+            "HDFCBANK",
+        ] {
+            assert_eq!(alias_key(spelling), "hdfcbank", "{}", spelling);
+        }
+        assert_eq!(alias_key(" .- "), "");
+    }
+
+    #[test]
+    fn search_patterns_escape_like_wildcards() {
+        assert_eq!(
+            search_patterns("H.D.F.C"),
+            Some(("%hdfc%".to_string(), "hdfc%".to_string()))
+        );
+        assert_eq!(
+            search_patterns("100%_"),
+            Some(("%100\\%\\_%".to_string(), "100\\%\\_%".to_string()))
+        );
+        assert_eq!(search_patterns(" - "), None);
+    }
+
+    #[tokio::test]
+    async fn bank_names_match_catalog_aliases() {
+        let app = TestApp::new().await;
+        for spelling in ["HDFC Bank", "h.d.f.c", "Hdfc-Bank"] {
+            let issuer = match_issuer(&app.db, spelling).await.unwrap();
+            let issuer = issuer.unwrap_or_else(|| panic!("{} did not match", spelling));
+            assert_eq!(issuer.issuer_id, "hdfc");
+            assert_eq!(issuer.primary_color, (0x00, 0x4C, 0x8F));
+            assert_eq!(issuer.secondary_color, (0xED, 0x23, 0x2A));
+        }
+        // Aliases are matched whole, not as substrings.
+        for spelling in ["HDFC Bank Ltd", "Test Bank", ""] {
+            assert!(
+                match_issuer(&app.db, spelling).await.unwrap().is_none(),
+                "{}",
+                spelling
+            );
+        }
+    }
+}
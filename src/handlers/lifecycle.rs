@@ -0,0 +1,259 @@
+use axum::{extract::State, http::StatusCode, Json};
+use nanoid::nanoid;
+use sqlx::{Sqlite, SqliteConnection, Transaction};
+use tracing::error;
+
+use crate::{
+    extractors::OwnedCard,
+    handlers::{
+        card::{invalidate_card_cache, load_card},
+        card_event,
+        common::AppError,
+    },
+    models::{AppState, CardStatus, ReplaceCardPayload, SetCardStatusPayload, ShowGetCardResponse},
+    money::Money,
+};
+
+fn conflict(message: String) -> AppError {
+    AppError(StatusCode::CONFLICT, message)
+}
+
+fn internal_error(e: sqlx::Error) -> AppError {
+    AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+}
+
+/// Closed is final and a lost card can't come back into use; every other move
+/// is allowed.
+fn transition_allowed(from: CardStatus, to: CardStatus) -> bool {
+    !matches!(
+        (from, to),
+        (CardStatus::Closed, _) | (CardStatus::Lost, CardStatus::Active | CardStatus::Frozen)
+    )
+}
+
+async fn record_revision(
+    conn: &mut SqliteConnection,
+    revision_id: &str,
+    card_id: &str,
+    field_name: &str,
+    old_value: &str,
+    new_value: &str,
+) -> Result<(), AppError> {
+    sqlx::query!(
+        "INSERT INTO card_revisions (revision_id, card_id, field_name, old_value, new_value) VALUES (?, ?, ?, ?, ?)",
+        revision_id,
+        card_id,
+        field_name,
+        old_value,
+        new_value
+    )
+    .execute(conn)
+    .await
+    .map_err(|e| {
+        error!("Error recording card revision {} ", e);
+        internal_error(e)
+    })?;
+    Ok(())
+}
+
+/// Moves the card from `from` to `to`. Fails with 409 if the card is no longer
+/// in `from`, so concurrent changes can't skip the transition rules.
+async fn write_status(
+    conn: &mut SqliteConnection,
+    revision_id: &str,
+    card_id: &str,
+    from: CardStatus,
+    to: CardStatus,
+) -> Result<(), AppError> {
+    let (from, to) = (from.as_str(), to.as_str());
+    let result = sqlx::query!(
+        "UPDATE cards SET status = ?, status_changed_at = CURRENT_TIMESTAMP WHERE card_id = ? AND status = ?",
+        to,
+        card_id,
+        from
+    )
+    .execute(&mut *conn)
+    .await
+    .map_err(internal_error)?;
+    if result.rows_affected() == 0 {
+        return Err(conflict(
+            "Card status changed in the meantime; reload and try again".to_string(),
+        ));
+    }
+    record_revision(conn, revision_id, card_id, "status", from, to).await
+}
+
+async fn finish(
+    state: &AppState,
+    mut tx: Transaction<'_, Sqlite>,
+    card_id: &str,
+    user_id: &str,
+) -> Result<Json<ShowGetCardResponse>, AppError> {
+    let card = load_card(&mut *tx, card_id, user_id)
+        .await?
+        .ok_or_else(|| AppError(StatusCode::NOT_FOUND, "Card not found".to_string()))?;
+    tx.commit().await.map_err(internal_error)?;
+    invalidate_card_cache(state, user_id).await;
+    Ok(Json(card))
+}
+
+pub async fn set_card_status(
+    State(state): State<AppState>,
+    OwnedCard {
+        card_id,
+        user_id,
+        status,
+        payload,
+        ..
+    }: OwnedCard<SetCardStatusPayload>,
+) -> Result<Json<ShowGetCardResponse>, AppError> {
+    if !transition_allowed(status, payload.status) {
+        return Err(conflict(format!(
+            "A {} card can't be marked {}",
+            status.as_str(),
+            payload.status.as_str()
+        )));
+    }
+
+    let mut tx = state.db.begin().await.map_err(internal_error)?;
+    if payload.status != status {
+        write_status(&mut tx, &nanoid!(), &card_id, status, payload.status).await?;
+    }
+    finish(&state, tx, &card_id, &user_id).await
+}
+
+/// Links a reissued card to the one it replaces. The old card is closed and,
+/// unless `inherit_balance` is false, its running balance moves to the
+/// replacement. Returns the replacement.
+pub async fn replace_card(
+    State(state): State<AppState>,
+    OwnedCard {
+        card_id,
+        user_id,
+        status,
+        payload,
+        ..
+    }: OwnedCard<ReplaceCardPayload>,
+) -> Result<Json<ShowGetCardResponse>, AppError> {
+    let replacement_id = payload.replacement_card_id;
+    let inherit_balance = payload.inherit_balance.unwrap_or(true);
+    if replacement_id == card_id {
+        return Err(AppError(
+            StatusCode::BAD_REQUEST,
+            "A card can't replace itself".to_string(),
+        ));
+    }
+
+    let mut tx = state.db.begin().await.map_err(internal_error)?;
+
+    let card = sqlx::query!(
+        "SELECT replaced_by, currency FROM cards WHERE card_id = ?",
+        card_id
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(internal_error)?;
+    if card.replaced_by.is_some() {
+        return Err(conflict("Card has already been replaced".to_string()));
+    }
+
+    let replacement = sqlx::query!(
+        r#"SELECT c.status, c.currency,
+                EXISTS (SELECT 1 FROM cards p WHERE p.replaced_by = c.card_id) as "replaces_another!: bool",
+                EXISTS (SELECT 1 FROM card_events e WHERE e.card_id = c.card_id) as "has_history!: bool"
+         FROM cards c
+         WHERE c.card_id = ? AND c.user_id = ? AND c.deleted_at IS NULL"#,
+        replacement_id,
+        user_id
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(internal_error)?
+    .ok_or_else(|| {
+        AppError(
+            StatusCode::NOT_FOUND,
+            "Replacement card not found".to_string(),
+        )
+    })?;
+    if replacement.status == CardStatus::Closed.as_str() {
+        return Err(conflict("The replacement card is closed".to_string()));
+    }
+    if replacement.replaces_another {
+        return Err(conflict(
+            "The replacement card already replaces another card".to_string(),
+        ));
+    }
+
+    // Linking must not close a loop: the card can't already be further down
+    // the replacement's own chain.
+    let in_chain = sqlx::query_scalar!(
+        r#"WITH RECURSIVE successors(card_id) AS (
+               SELECT replaced_by FROM cards WHERE card_id = ?
+               UNION
+               SELECT c.replaced_by FROM cards c JOIN successors s ON c.card_id = s.card_id
+           )
+           SELECT EXISTS (SELECT 1 FROM successors WHERE card_id = ?) as "found!: bool""#,
+        replacement_id,
+        card_id
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(internal_error)?;
+    if in_chain {
+        return Err(conflict(
+            "This card already comes after the replacement in its chain".to_string(),
+        ));
+    }
+
+    if inherit_balance {
+        if replacement.currency != card.currency {
+            return Err(AppError(
+                StatusCode::BAD_REQUEST,
+                "Cards use different currencies; link them with inherit_balance set to false"
+                    .to_string(),
+            ));
+        }
+        if replacement.has_history {
+            return Err(conflict(
+                "The replacement card already has history; link it with inherit_balance set to false".to_string(),
+            ));
+        }
+        // The new card only takes over the balance; the old card's delta was
+        // already moved.
+        let balance = sqlx::query_scalar!(
+            r#"SELECT last_total_due as "last_total_due!: Money" FROM card_running_state WHERE card_id = ?"#,
+            card_id
+        )
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(internal_error)?
+        .unwrap_or_default();
+        card_event::record_opening_balance(&mut tx, &replacement_id, balance)
+            .await
+            .map_err(internal_error)?;
+    }
+
+    let revision_id = nanoid!();
+    sqlx::query!(
+        "UPDATE cards SET replaced_by = ? WHERE card_id = ?",
+        replacement_id,
+        card_id
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(internal_error)?;
+    record_revision(
+        &mut tx,
+        &revision_id,
+        &card_id,
+        "replaced_by",
+        "",
+        &replacement_id,
+    )
+    .await?;
+    if status != CardStatus::Closed {
+        write_status(&mut tx, &revision_id, &card_id, status, CardStatus::Closed).await?;
+    }
+
+    finish(&state, tx, &replacement_id, &user_id).await
+}
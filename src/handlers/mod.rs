@@ -1,4 +1,15 @@
+pub mod billing;
 pub mod card;
+pub mod card_batch;
+pub mod card_event;
+pub mod card_list;
 pub mod color;
 pub mod common;
+pub mod currency;
+pub mod idempotency;
+pub mod issuer;
+pub mod lifecycle;
+pub mod statement;
+pub mod tag;
+pub mod theme;
 pub mod user;
@@ -0,0 +1,376 @@
+use axum::{extract::State, http::StatusCode, Json};
+use nanoid::nanoid;
+use sqlx::SqlitePool;
+use time::{macros::format_description, Date, OffsetDateTime};
+use tracing::error;
+
+use crate::{
+    extractors::OwnedCard,
+    handlers::{billing, card_event, common::AppError},
+    models::{
+        AppState, CardStatement, CardStatementDetail, CardStatus, CardTransactionHistory,
+        CloseStatementPayload, EventType, GetStatementPayload, GetStatementsPayload, PaymentStatus,
+        UpdateStatementPaymentPayload,
+    },
+    money::Money,
+};
+
+/// Closes the current cycle now. Like the scheduled run, it leaves archived
+/// and closed cards alone.
+pub async fn close_statement(
+    State(state): State<AppState>,
+    OwnedCard {
+        card_id,
+        archived,
+        status,
+        ..
+    }: OwnedCard<CloseStatementPayload>,
+) -> Result<Json<CardStatementDetail>, AppError> {
+    if archived {
+        return Err(AppError(
+            StatusCode::CONFLICT,
+            "Card is archived; unarchive it before closing a statement".to_string(),
+        ));
+    }
+    if status == CardStatus::Closed {
+        return Err(AppError(
+            StatusCode::CONFLICT,
+            "Card is closed; its statements continue on its replacement".to_string(),
+        ));
+    }
+
+    let statement_id = close_cycle(&state.db, &card_id, None)
+        .await
+        .map_err(|e| {
+            error!("Error closing statement {}", e);
+            AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+        })?
+        .ok_or_else(|| {
+            AppError(
+                StatusCode::CONFLICT,
+                "A statement for this card was closed moments ago".to_string(),
+            )
+        })?;
+
+    load_statement_detail(&state.db, &card_id, &statement_id).await
+}
+
+pub async fn get_statements(
+    State(state): State<AppState>,
+    OwnedCard {
+        card_id, payload, ..
+    }: OwnedCard<GetStatementsPayload>,
+) -> Result<Json<Vec<CardStatement>>, AppError> {
+    let statements = sqlx::query_as!(
+        CardStatement,
+        r#"
+        WITH RECURSIVE chain(card_id) AS (
+            SELECT ?
+            UNION
+            SELECT c.card_id FROM cards c JOIN chain ON c.replaced_by = chain.card_id
+            WHERE ?
+        )
+        SELECT statement_id as "statement_id!",
+        card_id,
+        cycle_start as "cycle_start!: String",
+        cycle_end as "cycle_end!: String",
+        closing_total_due as "closing_total_due!: Money",
+        closing_delta as "closing_delta!: Money",
+        due_date as "due_date: String",
+        payment_status,
+        amount_paid as "amount_paid!: Money",
+        paid_at as "paid_at: String",
+        closed_at as "closed_at!: String"
+        FROM card_statements
+        WHERE card_id IN (SELECT card_id FROM chain)
+        ORDER BY cycle_end DESC"#,
+        card_id,
+        payload.include_predecessors
+    )
+    .fetch_all(&state.db)
+    .await
+    .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(statements))
+}
+
+pub async fn get_statement(
+    State(state): State<AppState>,
+    OwnedCard {
+        card_id, payload, ..
+    }: OwnedCard<GetStatementPayload>,
+) -> Result<Json<CardStatementDetail>, AppError> {
+    load_statement_detail(&state.db, &card_id, &payload.statement_id).await
+}
+
+pub async fn update_payment(
+    State(state): State<AppState>,
+    OwnedCard {
+        card_id, payload, ..
+    }: OwnedCard<UpdateStatementPaymentPayload>,
+) -> Result<Json<CardStatementDetail>, AppError> {
+    let closing_total_due = sqlx::query_scalar!(
+        r#"SELECT closing_total_due as "closing_total_due: Money" FROM card_statements WHERE statement_id = ? AND card_id = ?"#,
+        payload.statement_id,
+        card_id
+    )
+    .fetch_optional(&state.db)
+    .await
+    .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .ok_or_else(statement_not_found)?;
+
+    let amount_paid = match (payload.payment_status, payload.amount_paid) {
+        (PaymentStatus::Unpaid, _) => Money::ZERO,
+        (PaymentStatus::Paid, None) => closing_total_due,
+        (PaymentStatus::Paid, Some(amount)) if amount >= closing_total_due => amount,
+        (PaymentStatus::Paid, Some(_)) => {
+            return Err(AppError(
+                StatusCode::BAD_REQUEST,
+                "A statement is only paid once amount_paid covers the statement total; record less as partially_paid"
+                    .to_string(),
+            ));
+        }
+        (PaymentStatus::PartiallyPaid, Some(amount))
+            if amount.is_positive() && amount < closing_total_due =>
+        {
+            amount
+        }
+        (PaymentStatus::PartiallyPaid, _) => {
+            return Err(AppError(
+                StatusCode::BAD_REQUEST,
+                "A partial payment needs an amount_paid between zero and the statement total"
+                    .to_string(),
+            ));
+        }
+    };
+
+    if amount_paid.is_negative() {
+        return Err(AppError(
+            StatusCode::BAD_REQUEST,
+            "amount_paid must be a positive amount".to_string(),
+        ));
+    }
+
+    let payment_status = payload.payment_status.as_str();
+    sqlx::query!(
+        "UPDATE card_statements
+         SET payment_status = ?,
+             amount_paid = ?,
+             paid_at = CASE WHEN ? = 'unpaid' THEN NULL ELSE CURRENT_TIMESTAMP END
+         WHERE statement_id = ? AND card_id = ?",
+        payment_status,
+        amount_paid,
+        payment_status,
+        payload.statement_id,
+        card_id
+    )
+    .execute(&state.db)
+    .await
+    .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    load_statement_detail(&state.db, &card_id, &payload.statement_id).await
+}
+
+/// Closes every statement date that has passed since each card's last
+/// statement, oldest first, so cycles missed while the server was down are
+/// still closed. Cards that are archived or closed are left alone.
+pub async fn close_due_statements(db: &SqlitePool) -> Result<u64, sqlx::Error> {
+    let today = OffsetDateTime::now_utc().date();
+    let cards = sqlx::query!(
+        r#"SELECT c.card_id as "card_id!", c.statement_day as "statement_day!",
+                (SELECT date(MIN(e.timestamp)) FROM card_events e
+                 WHERE e.card_id = c.card_id) as "first_event_on?: String",
+                (SELECT date(MAX(s.cycle_end)) FROM card_statements s
+                 WHERE s.card_id = c.card_id) as "last_statement_on?: String"
+         FROM cards c
+         WHERE c.statement_day IS NOT NULL
+           AND c.deleted_at IS NULL
+           AND c.archived_at IS NULL
+           AND c.status <> 'closed'"#
+    )
+    .fetch_all(db)
+    .await?;
+
+    let mut closed = 0;
+    for card in cards {
+        let statement_day = card.statement_day as u8;
+        // The first statement date after the last statement or, for a card
+        // that never had one, on or after its first transaction.
+        let mut statement_date = match (
+            card.last_statement_on.as_deref().and_then(parse_date),
+            card.first_event_on.as_deref().and_then(parse_date),
+        ) {
+            (Some(last), _) => {
+                billing::next_statement_date(last.next_day().unwrap_or(last), statement_day)
+            }
+            (None, Some(first)) => billing::next_statement_date(first, statement_day),
+            (None, None) => billing::next_statement_date(today, statement_day),
+        };
+        while statement_date <= today {
+            if close_cycle(db, &card.card_id, Some(statement_date))
+                .await?
+                .is_some()
+            {
+                closed += 1;
+            }
+            let Some(next_day) = statement_date.next_day() else {
+                break;
+            };
+            statement_date = billing::next_statement_date(next_day, statement_day);
+        }
+    }
+
+    Ok(closed)
+}
+
+fn parse_date(value: &str) -> Option<Date> {
+    Date::parse(value, format_description!("[year]-[month]-[day]")).ok()
+}
+
+/// Closes the card's current cycle and returns the new statement id, or
+/// `None` if the previous statement already ends there.
+///
+/// With a `statement_date` the cycle ends with that day (or now, if that is
+/// earlier) and the payment is due relative to it; without one the cycle is
+/// closed at the current time.
+async fn close_cycle(
+    db: &SqlitePool,
+    card_id: &str,
+    statement_date: Option<Date>,
+) -> Result<Option<String>, sqlx::Error> {
+    let mut tx = db.begin().await?;
+
+    let now = sqlx::query_scalar!(r#"SELECT CURRENT_TIMESTAMP as "now!: String""#)
+        .fetch_one(&mut *tx)
+        .await?;
+    let (cycle_end, statement_date) = match statement_date {
+        Some(date) => (format!("{} 23:59:59", date).min(now), date),
+        None => (now, OffsetDateTime::now_utc().date()),
+    };
+
+    let previous_end = sqlx::query_scalar!(
+        r#"SELECT MAX(cycle_end) as "cycle_end: String" FROM card_statements WHERE card_id = ?"#,
+        card_id
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    if previous_end
+        .as_deref()
+        .is_some_and(|previous_end| previous_end >= cycle_end.as_str())
+    {
+        return Ok(None);
+    }
+
+    let cycle_start = match &previous_end {
+        Some(previous_end) => previous_end.clone(),
+        None => sqlx::query_scalar!(
+            r#"SELECT MIN(timestamp) as "timestamp: String" FROM card_events WHERE card_id = ?"#,
+            card_id
+        )
+        .fetch_one(&mut *tx)
+        .await?
+        .unwrap_or_else(|| cycle_end.clone()),
+    };
+
+    let rule = sqlx::query!(
+        "SELECT payment_due_day, payment_due_offset_days FROM cards WHERE card_id = ?",
+        card_id
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+    let due_date = billing::due_rule(rule.payment_due_day, rule.payment_due_offset_days)
+        .map(|rule| billing::due_date(statement_date, rule).to_string());
+    // The balance as it stood when the cycle ended, not as it is now.
+    let closing = card_event::state_at(&mut *tx, card_id, &cycle_end).await?;
+
+    let statement_id = nanoid!();
+    sqlx::query!(
+        "INSERT INTO card_statements (statement_id, card_id, cycle_start, cycle_end, closing_total_due, closing_delta, due_date)
+         VALUES (?, ?, ?, ?, ?, ?, ?)",
+        statement_id,
+        card_id,
+        cycle_start,
+        cycle_end,
+        closing.last_total_due,
+        closing.last_delta,
+        due_date
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query!(
+        "INSERT INTO card_statement_events (statement_id, transaction_id, total_due_input, timestamp, event_type, amount, merchant, category)
+         SELECT ?, transaction_id, total_due_input, timestamp, event_type, amount, merchant, category
+         FROM card_events
+         WHERE card_id = ? AND timestamp <= ? AND (? IS NULL OR timestamp > ?)",
+        statement_id,
+        card_id,
+        cycle_end,
+        previous_end,
+        previous_end
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(Some(statement_id))
+}
+
+async fn load_statement_detail(
+    db: &SqlitePool,
+    card_id: &str,
+    statement_id: &str,
+) -> Result<Json<CardStatementDetail>, AppError> {
+    let statement = sqlx::query_as!(
+        CardStatement,
+        r#"
+        SELECT statement_id as "statement_id!",
+        card_id,
+        cycle_start as "cycle_start!: String",
+        cycle_end as "cycle_end!: String",
+        closing_total_due as "closing_total_due!: Money",
+        closing_delta as "closing_delta!: Money",
+        due_date as "due_date: String",
+        payment_status,
+        amount_paid as "amount_paid!: Money",
+        paid_at as "paid_at: String",
+        closed_at as "closed_at!: String"
+        FROM card_statements
+        WHERE statement_id = ? AND card_id = ?"#,
+        statement_id,
+        card_id
+    )
+    .fetch_optional(db)
+    .await
+    .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .ok_or_else(statement_not_found)?;
+
+    let events = sqlx::query_as!(
+        CardTransactionHistory,
+        r#"
+        SELECT e.transaction_id as "transaction_id!",
+        s.card_id as "card_id!",
+        e.total_due_input as "total_due_input!: Money",
+        e.timestamp as "timestamp!: String",
+        e.event_type as "event_type!: EventType",
+        e.amount as "amount: Money",
+        e.merchant,
+        e.category
+        FROM card_statement_events e
+        JOIN card_statements s ON s.statement_id = e.statement_id
+        WHERE e.statement_id = ?
+        ORDER BY e.timestamp DESC"#,
+        statement_id
+    )
+    .fetch_all(db)
+    .await
+    .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(CardStatementDetail { statement, events }))
+}
+
+fn statement_not_found() -> AppError {
+    AppError(StatusCode::NOT_FOUND, "Statement not found".to_string())
+}
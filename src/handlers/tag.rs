@@ -0,0 +1,242 @@
+use axum::{extract::State, http::StatusCode, Extension, Json};
+use nanoid::nanoid;
+use tracing::error;
+
+use crate::{
+    extractors::OwnedCard,
+    handlers::{card::invalidate_card_cache, common::AppError},
+    models::{
+        AppState, CreateTagPayload, DeleteTagPayload, SetCardTagsPayload, Tag, TagCurrencyTotal,
+        TagSummary, UpdateTagPayload,
+    },
+    money::Money,
+};
+
+const MAX_TAG_NAME_LEN: usize = 40;
+
+fn tag_name(name: &str) -> Result<String, AppError> {
+    let name = name.trim();
+    if name.is_empty() || name.chars().count() > MAX_TAG_NAME_LEN {
+        return Err(AppError(
+            StatusCode::BAD_REQUEST,
+            format!("Tag name must be 1 to {} characters", MAX_TAG_NAME_LEN),
+        ));
+    }
+    Ok(name.to_string())
+}
+
+fn tag_write_error(e: sqlx::Error) -> AppError {
+    if e.as_database_error()
+        .is_some_and(|e| e.is_unique_violation())
+    {
+        return AppError(
+            StatusCode::CONFLICT,
+            "A tag with this name already exists".to_string(),
+        );
+    }
+    error!("Error saving tag {}", e);
+    AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+}
+
+fn tag_not_found() -> AppError {
+    AppError(StatusCode::NOT_FOUND, "Tag not found".to_string())
+}
+
+/// The user's tags with card counts and per-currency totals over active cards.
+/// A card that has been replaced no longer counts towards its tags, even if its
+/// successor isn't tagged the same way.
+pub async fn get_tags(
+    State(state): State<AppState>,
+    Extension((user_id, _role)): Extension<(String, String)>,
+) -> Result<Json<Vec<TagSummary>>, AppError> {
+    let rows = sqlx::query!(
+        r#"SELECT t.tag_id as "tag_id!", t.name,
+                c.currency as "currency?: String",
+                COUNT(c.card_id) as "card_count!: i64",
+                COALESCE(SUM(crs.last_total_due), 0) as "last_total_due!: Money",
+                COALESCE(SUM(crs.last_delta), 0) as "last_delta!: Money"
+         FROM tags t
+         LEFT JOIN card_tags ct ON ct.tag_id = t.tag_id
+         LEFT JOIN cards c ON c.card_id = ct.card_id
+              AND c.deleted_at IS NULL AND c.archived_at IS NULL AND c.replaced_by IS NULL
+         LEFT JOIN card_running_state crs ON crs.card_id = c.card_id
+         WHERE t.user_id = ?
+         GROUP BY t.tag_id, c.currency
+         ORDER BY t.name COLLATE NOCASE, t.tag_id, c.currency"#,
+        user_id
+    )
+    .fetch_all(&state.db)
+    .await
+    .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let mut tags: Vec<TagSummary> = Vec::new();
+    for row in rows {
+        if tags.last().is_none_or(|tag| tag.tag_id != row.tag_id) {
+            tags.push(TagSummary {
+                tag_id: row.tag_id.clone(),
+                name: row.name.clone(),
+                card_count: 0,
+                totals: Vec::new(),
+            });
+        }
+        let tag = tags.last_mut().expect("pushed above");
+        // A tag with no active cards still comes back as one row with a NULL currency.
+        if let Some(currency) = row.currency {
+            tag.card_count += row.card_count;
+            tag.totals.push(TagCurrencyTotal {
+                currency,
+                last_total_due: row.last_total_due,
+                last_delta: row.last_delta,
+            });
+        }
+    }
+
+    Ok(Json(tags))
+}
+
+pub async fn create_tag(
+    State(state): State<AppState>,
+    Extension((user_id, _role)): Extension<(String, String)>,
+    Json(payload): Json<CreateTagPayload>,
+) -> Result<Json<Tag>, AppError> {
+    let name = tag_name(&payload.name)?;
+    let tag_id = nanoid!();
+
+    sqlx::query!(
+        "INSERT INTO tags (tag_id, user_id, name) VALUES (?, ?, ?)",
+        tag_id,
+        user_id,
+        name
+    )
+    .execute(&state.db)
+    .await
+    .map_err(tag_write_error)?;
+
+    Ok(Json(Tag { tag_id, name }))
+}
+
+pub async fn rename_tag(
+    State(state): State<AppState>,
+    Extension((user_id, _role)): Extension<(String, String)>,
+    Json(payload): Json<UpdateTagPayload>,
+) -> Result<Json<Tag>, AppError> {
+    let name = tag_name(&payload.name)?;
+
+    let result = sqlx::query!(
+        "UPDATE tags SET name = ? WHERE tag_id = ? AND user_id = ?",
+        name,
+        payload.tag_id,
+        user_id
+    )
+    .execute(&state.db)
+    .await
+    .map_err(tag_write_error)?;
+
+    if result.rows_affected() == 0 {
+        return Err(tag_not_found());
+    }
+
+    invalidate_card_cache(&state, &user_id).await;
+
+    Ok(Json(Tag {
+        tag_id: payload.tag_id,
+        name,
+    }))
+}
+
+pub async fn delete_tag(
+    State(state): State<AppState>,
+    Extension((user_id, _role)): Extension<(String, String)>,
+    Json(payload): Json<DeleteTagPayload>,
+) -> Result<Json<bool>, AppError> {
+    let result = sqlx::query!(
+        "DELETE FROM tags WHERE tag_id = ? AND user_id = ?",
+        payload.tag_id,
+        user_id
+    )
+    .execute(&state.db)
+    .await
+    .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    if result.rows_affected() == 0 {
+        return Err(tag_not_found());
+    }
+
+    invalidate_card_cache(&state, &user_id).await;
+
+    Ok(Json(true))
+}
+
+/// Replaces the card's tags with `tag_ids`; an empty list clears them.
+pub async fn set_card_tags(
+    State(state): State<AppState>,
+    OwnedCard {
+        card_id,
+        user_id,
+        payload,
+        ..
+    }: OwnedCard<SetCardTagsPayload>,
+) -> Result<Json<Vec<Tag>>, AppError> {
+    let mut tag_ids = payload.tag_ids;
+    tag_ids.sort();
+    tag_ids.dedup();
+
+    let mut tx = state.db.begin().await.map_err(|e| {
+        error!("Error starting transaction: {}", e);
+        AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+    })?;
+
+    for tag_id in &tag_ids {
+        let owned = sqlx::query_scalar!(
+            r#"SELECT 1 as "found!: i64" FROM tags WHERE tag_id = ? AND user_id = ?"#,
+            tag_id,
+            user_id
+        )
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        if owned.is_none() {
+            return Err(AppError(
+                StatusCode::NOT_FOUND,
+                format!("Tag {} not found", tag_id),
+            ));
+        }
+    }
+
+    sqlx::query!("DELETE FROM card_tags WHERE card_id = ?", card_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    for tag_id in &tag_ids {
+        sqlx::query!(
+            "INSERT INTO card_tags (card_id, tag_id) VALUES (?, ?)",
+            card_id,
+            tag_id
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    }
+
+    let tags = sqlx::query_as!(
+        Tag,
+        r#"SELECT t.tag_id as "tag_id!", t.name
+         FROM card_tags ct
+         JOIN tags t ON t.tag_id = ct.tag_id
+         WHERE ct.card_id = ?
+         ORDER BY t.name COLLATE NOCASE"#,
+        card_id
+    )
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    tx.commit()
+        .await
+        .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    invalidate_card_cache(&state, &user_id).await;
+
+    Ok(Json(tags))
+}
@@ -0,0 +1,487 @@
+use axum::{http::StatusCode, Json};
+
+use crate::{
+    handlers::{
+        color::{self, Color, BLACK, WHITE},
+        common::AppError,
+    },
+    models::{CardTheme, CardThemeInput},
+};
+
+pub const DEFAULT_GRADIENT_ANGLE: u16 = 135;
+
+/// Minimum contrast between the text color and every part of the card. This is
+/// WCAG AA for large text, which is what the card face shows.
+pub const MIN_TEXT_CONTRAST: f64 = 3.0;
+
+#[derive(PartialEq)]
+pub struct ThemePreset {
+    pub name: &'static str,
+    pub primary_color: (u8, u8, u8),
+    pub secondary_color: (u8, u8, u8),
+    pub gradient_angle: u16,
+}
+
+pub const PRESETS: &[ThemePreset] = &[
+    ThemePreset {
+        name: "midnight",
+        primary_color: (0x0F, 0x20, 0x27),
+        secondary_color: (0x2C, 0x53, 0x64),
+        gradient_angle: 135,
+    },
+    ThemePreset {
+        name: "graphite",
+        primary_color: (0x23, 0x25, 0x26),
+        secondary_color: (0x41, 0x43, 0x45),
+        gradient_angle: 135,
+    },
+    ThemePreset {
+        name: "slate",
+        primary_color: (0x33, 0x41, 0x55),
+        secondary_color: (0x47, 0x55, 0x69),
+        gradient_angle: 160,
+    },
+    ThemePreset {
+        name: "ocean",
+        primary_color: (0x1A, 0x5F, 0x7A),
+        secondary_color: (0x15, 0x98, 0x95),
+        gradient_angle: 120,
+    },
+    ThemePreset {
+        name: "forest",
+        primary_color: (0x13, 0x4E, 0x5E),
+        secondary_color: (0x2E, 0x7D, 0x32),
+        gradient_angle: 135,
+    },
+    ThemePreset {
+        name: "sunset",
+        primary_color: (0xC0, 0x39, 0x2B),
+        secondary_color: (0x8E, 0x44, 0xAD),
+        gradient_angle: 45,
+    },
+    ThemePreset {
+        name: "gold",
+        primary_color: (0xF7, 0xD7, 0x74),
+        secondary_color: (0xD4, 0xA0, 0x17),
+        gradient_angle: 135,
+    },
+    ThemePreset {
+        name: "rose",
+        primary_color: (0xF8, 0xC8, 0xDC),
+        secondary_color: (0xF4, 0xA6, 0xC0),
+        gradient_angle: 90,
+    },
+];
+
+/// A card's stored look: the gradient stops, its direction and the preset it
+/// still matches, if any.
+#[derive(Clone, Copy, PartialEq)]
+pub struct Theme {
+    pub primary_color: Color,
+    pub secondary_color: Color,
+    pub gradient_angle: u16,
+    pub preset: Option<&'static ThemePreset>,
+}
+
+impl ThemePreset {
+    fn matches(&self, primary: Color, secondary: Color, gradient_angle: u16) -> bool {
+        primary == Color::opaque(self.primary_color)
+            && secondary == Color::opaque(self.secondary_color)
+            && gradient_angle == self.gradient_angle
+    }
+
+    fn card_theme(&'static self) -> CardTheme {
+        Theme {
+            primary_color: Color::opaque(self.primary_color),
+            secondary_color: Color::opaque(self.secondary_color),
+            gradient_angle: self.gradient_angle,
+            preset: Some(self),
+        }
+        .card_theme()
+    }
+}
+
+fn find_preset(name: &str) -> Option<&'static ThemePreset> {
+    PRESETS
+        .iter()
+        .find(|preset| preset.name.eq_ignore_ascii_case(name.trim()))
+}
+
+fn bad_request(message: String) -> AppError {
+    AppError(StatusCode::BAD_REQUEST, message)
+}
+
+fn parse_color(field: &str, value: Option<&str>) -> Result<Option<Color>, AppError> {
+    value
+        .map(|value| {
+            Color::parse_hex(value).ok_or_else(|| {
+                bad_request(format!(
+                    "{} must be a hex color like #1a2b3c or #1a2b3c80",
+                    field
+                ))
+            })
+        })
+        .transpose()
+}
+
+impl Theme {
+    /// Opaque colors at the default angle.
+    pub fn new(primary_color: (u8, u8, u8), secondary_color: (u8, u8, u8)) -> Self {
+        Theme {
+            primary_color: Color::opaque(primary_color),
+            secondary_color: Color::opaque(secondary_color),
+            gradient_angle: DEFAULT_GRADIENT_ANGLE,
+            preset: None,
+        }
+    }
+
+    /// Rebuilds a theme from the card's columns. An unknown preset name (say, one
+    /// that was retired) is dropped rather than failing the read.
+    pub fn from_row(
+        primary_color: i64,
+        primary_alpha: Option<i64>,
+        secondary_color: i64,
+        secondary_alpha: Option<i64>,
+        gradient_angle: i64,
+        preset: Option<&str>,
+    ) -> Self {
+        Theme {
+            primary_color: Color {
+                rgb: color::unpack(primary_color),
+                alpha: primary_alpha.map(|alpha| alpha as u8),
+            },
+            secondary_color: Color {
+                rgb: color::unpack(secondary_color),
+                alpha: secondary_alpha.map(|alpha| alpha as u8),
+            },
+            gradient_angle: gradient_angle as u16,
+            preset: preset.and_then(find_preset),
+        }
+    }
+
+    /// Starts from `base` (the card's current theme, if any) and applies the legacy
+    /// color fields and then `input`, so `input` wins where both are given. Fails if
+    /// no colors can be worked out or the result isn't readable.
+    pub fn resolve(
+        base: Option<Theme>,
+        primary_color: Option<(u8, u8, u8)>,
+        secondary_color: Option<(u8, u8, u8)>,
+        input: Option<&CardThemeInput>,
+    ) -> Result<Theme, AppError> {
+        let requested_preset = input
+            .and_then(|input| input.preset.as_deref())
+            .map(|name| {
+                find_preset(name)
+                    .ok_or_else(|| bad_request(format!("Unknown theme preset '{}'", name)))
+            })
+            .transpose()?;
+        let input_primary = parse_color(
+            "theme.primary_color",
+            input.and_then(|input| input.primary_color.as_deref()),
+        )?;
+        let input_secondary = parse_color(
+            "theme.secondary_color",
+            input.and_then(|input| input.secondary_color.as_deref()),
+        )?;
+        let input_angle = input.and_then(|input| input.gradient_angle);
+        if input_angle.is_some_and(|angle| angle >= 360) {
+            return Err(bad_request(
+                "theme.gradient_angle must be between 0 and 359".to_string(),
+            ));
+        }
+
+        let pick = |from_input: Option<Color>,
+                    from_preset: Option<(u8, u8, u8)>,
+                    legacy: Option<(u8, u8, u8)>,
+                    current: Option<Color>| {
+            from_input
+                .or(from_preset.map(Color::opaque))
+                .or(legacy.map(Color::opaque))
+                .or(current)
+        };
+        let primary = pick(
+            input_primary,
+            requested_preset.map(|preset| preset.primary_color),
+            primary_color,
+            base.map(|theme| theme.primary_color),
+        );
+        let secondary = pick(
+            input_secondary,
+            requested_preset.map(|preset| preset.secondary_color),
+            secondary_color,
+            base.map(|theme| theme.secondary_color),
+        );
+        let (Some(primary), Some(secondary)) = (primary, secondary) else {
+            return Err(bad_request(
+                "Card colors are required for banks that are not in the issuer catalog; pass a theme or card_primary_color and card_secondary_color".to_string(),
+            ));
+        };
+        let gradient_angle = input_angle
+            .or(requested_preset.map(|preset| preset.gradient_angle))
+            .or(base.map(|theme| theme.gradient_angle))
+            .unwrap_or(DEFAULT_GRADIENT_ANGLE);
+
+        // The preset name sticks only while the card still looks exactly like it.
+        let preset = requested_preset
+            .or(base.and_then(|theme| theme.preset))
+            .filter(|preset| preset.matches(primary, secondary, gradient_angle));
+
+        let theme = Theme {
+            primary_color: primary,
+            secondary_color: secondary,
+            gradient_angle,
+            preset,
+        };
+        if base != Some(theme) {
+            theme.check_readable()?;
+        }
+        Ok(theme)
+    }
+
+    /// Black or white, whichever contrasts better with both gradient stops, and
+    /// the worst contrast it reaches. Translucent stops are judged over both a
+    /// light and a dark backdrop since the page behind the card can be either.
+    pub fn text_color(&self) -> ((u8, u8, u8), f64) {
+        let surfaces: Vec<(u8, u8, u8)> = [self.primary_color, self.secondary_color]
+            .into_iter()
+            .flat_map(|color| [color.over(WHITE), color.over(BLACK)])
+            .collect();
+        let worst = |text: (u8, u8, u8)| {
+            surfaces
+                .iter()
+                .map(|surface| color::contrast_ratio(text, *surface))
+                .fold(f64::INFINITY, f64::min)
+        };
+        let (white, black) = (worst(WHITE), worst(BLACK));
+        if white >= black {
+            (WHITE, white)
+        } else {
+            (BLACK, black)
+        }
+    }
+
+    pub fn check_readable(&self) -> Result<(), AppError> {
+        let (_, contrast) = self.text_color();
+        if contrast < MIN_TEXT_CONTRAST {
+            return Err(bad_request(format!(
+                "Colors {} and {} leave text unreadable: best contrast is {:.2}:1, at least {:.1}:1 is required",
+                self.primary_color.to_hex(),
+                self.secondary_color.to_hex(),
+                contrast,
+                MIN_TEXT_CONTRAST
+            )));
+        }
+        Ok(())
+    }
+
+    pub fn card_theme(&self) -> CardTheme {
+        CardTheme {
+            primary_color: self.primary_color.to_hex(),
+            secondary_color: self.secondary_color.to_hex(),
+            gradient_angle: self.gradient_angle,
+            text_color: color::to_hex(self.text_color().0),
+            preset: self.preset.map(|preset| preset.name.to_string()),
+        }
+    }
+}
+
+impl From<CardTheme> for crate::proto::CardTheme {
+    fn from(theme: CardTheme) -> Self {
+        crate::proto::CardTheme {
+            primary_color: theme.primary_color,
+            secondary_color: theme.secondary_color,
+            gradient_angle: i32::from(theme.gradient_angle),
+            text_color: theme.text_color,
+            preset: theme.preset,
+        }
+    }
+}
+
+/// Named themes a card can start from.
+pub async fn get_presets() -> Json<Vec<CardTheme>> {
+    Json(PRESETS.iter().map(ThemePreset::card_theme).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn input(
+        preset: Option<&str>,
+        primary_color: Option<&str>,
+        secondary_color: Option<&str>,
+        gradient_angle: Option<u16>,
+    ) -> CardThemeInput {
+        CardThemeInput {
+            preset: preset.map(str::to_string),
+            primary_color: primary_color.map(str::to_string),
+            secondary_color: secondary_color.map(str::to_string),
+            gradient_angle,
+        }
+    }
+
+    fn preset_name(theme: &Theme) -> Option<&'static str> {
+        theme.preset.map(|preset| preset.name)
+    }
+
+    fn status(result: Result<Theme, AppError>) -> Option<StatusCode> {
+        result.err().map(|e| e.0)
+    }
+
+    #[test]
+    fn presets_are_readable() {
+        for preset in PRESETS {
+            let theme = Theme::resolve(
+                None,
+                None,
+                None,
+                Some(&input(Some(preset.name), None, None, None)),
+            );
+            assert!(theme.is_ok(), "{}", preset.name);
+        }
+    }
+
+    #[test]
+    fn preset_sticks_while_the_card_still_matches_it() {
+        let ocean = Theme::resolve(
+            None,
+            None,
+            None,
+            Some(&input(Some("Ocean"), None, None, None)),
+        )
+        .ok()
+        .unwrap();
+        assert_eq!(preset_name(&ocean), Some("ocean"));
+        assert_eq!(ocean.gradient_angle, 120);
+
+        // Renaming the card or resending the same colors keeps it.
+        let same = Theme::resolve(Some(ocean), None, None, None).ok().unwrap();
+        assert_eq!(preset_name(&same), Some("ocean"));
+        let same = Theme::resolve(
+            Some(ocean),
+            None,
+            None,
+            Some(&input(None, Some("#1a5f7a"), None, Some(120))),
+        )
+        .ok()
+        .unwrap();
+        assert_eq!(preset_name(&same), Some("ocean"));
+
+        // Any change to the look drops it.
+        let turned = Theme::resolve(
+            Some(ocean),
+            None,
+            None,
+            Some(&input(None, None, None, Some(90))),
+        )
+        .ok()
+        .unwrap();
+        assert_eq!(preset_name(&turned), None);
+        let recolored = Theme::resolve(Some(ocean), Some((0x10, 0x20, 0x30)), None, None)
+            .ok()
+            .unwrap();
+        assert_eq!(preset_name(&recolored), None);
+    }
+
+    #[test]
+    fn input_overrides_the_preset_and_legacy_colors() {
+        let theme = Theme::resolve(
+            None,
+            Some((0x00, 0x00, 0x00)),
+            Some((0x00, 0x00, 0x00)),
+            Some(&input(Some("midnight"), Some("#1a2b3c80"), None, None)),
+        )
+        .ok()
+        .unwrap();
+        assert!(
+            theme.primary_color
+                == Color {
+                    rgb: (0x1A, 0x2B, 0x3C),
+                    alpha: Some(0x80),
+                }
+        );
+        assert!(theme.secondary_color == Color::opaque((0x2C, 0x53, 0x64)));
+        assert_eq!(theme.gradient_angle, 135);
+        assert_eq!(preset_name(&theme), None);
+    }
+
+    #[test]
+    fn rejects_bad_input() {
+        let bad = [
+            input(Some("neon"), None, None, None),
+            input(Some("ocean"), Some("1a2b3c"), None, None),
+            input(Some("ocean"), None, Some("#abc"), None),
+            input(Some("ocean"), None, None, Some(360)),
+        ];
+        for theme in &bad {
+            assert_eq!(
+                status(Theme::resolve(None, None, None, Some(theme))),
+                Some(StatusCode::BAD_REQUEST)
+            );
+        }
+        // Nothing to take the colors from.
+        assert_eq!(
+            status(Theme::resolve(None, None, None, None)),
+            Some(StatusCode::BAD_REQUEST)
+        );
+    }
+
+    #[test]
+    fn rejects_colors_just_under_the_contrast_threshold() {
+        // Black text on #595959 reaches 2.998:1; on #5a5a5a it reaches 3.04:1.
+        let under = Theme::new((0x59, 0x59, 0x59), WHITE);
+        let (_, contrast) = under.text_color();
+        assert!(contrast < MIN_TEXT_CONTRAST && contrast > 2.99);
+        assert_eq!(
+            under.check_readable().err().map(|e| e.0),
+            Some(StatusCode::BAD_REQUEST)
+        );
+        assert_eq!(
+            status(Theme::resolve(
+                None,
+                Some((0x59, 0x59, 0x59)),
+                Some(WHITE),
+                None
+            )),
+            Some(StatusCode::BAD_REQUEST)
+        );
+
+        let over = Theme::new((0x5A, 0x5A, 0x5A), WHITE);
+        assert_eq!(over.text_color().0, BLACK);
+        assert!(over.check_readable().is_ok());
+    }
+
+    #[test]
+    fn unchanged_themes_are_not_rechecked() {
+        // Stored before the contrast rule existed.
+        let legacy = Theme::new(BLACK, WHITE);
+        assert!(legacy.check_readable().is_err());
+        assert!(Theme::resolve(Some(legacy), None, None, None).is_ok());
+    }
+
+    #[test]
+    fn text_color_judges_translucent_stops_on_light_and_dark() {
+        let theme = Theme::new(BLACK, BLACK);
+        assert_eq!(theme.text_color(), (WHITE, 21.0));
+
+        // Faint enough that a light page shows through.
+        let faint = Theme {
+            primary_color: Color {
+                rgb: BLACK,
+                alpha: Some(0x20),
+            },
+            ..theme
+        };
+        assert!(faint.text_color().1 < theme.text_color().1);
+        assert!(faint.check_readable().is_err());
+    }
+
+    #[test]
+    fn from_row_drops_unknown_presets() {
+        let theme = Theme::from_row(0x1A5F7A, None, 0x159895, None, 120, Some("retired"));
+        assert_eq!(preset_name(&theme), None);
+        let theme = Theme::from_row(0x1A5F7A, None, 0x159895, Some(0x80), 120, Some("Ocean"));
+        assert_eq!(preset_name(&theme), Some("ocean"));
+        assert_eq!(theme.secondary_color.to_hex(), "#15989580");
+    }
+}
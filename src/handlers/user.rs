@@ -1,9 +1,12 @@
-use crate::models::{AppState, GetUserResponse, GetUsers};
+use crate::handlers::currency::currency_code_or_400;
+use crate::models::{
+    AppState, GetUserResponse, GetUsers, HomeCurrencyResponse, SetHomeCurrencyPayload,
+};
 use axum::{
     extract::State,
     http::StatusCode,
     response::{IntoResponse, Response},
-    Json,
+    Extension, Json,
 };
 use tracing::error;
 
@@ -53,3 +56,47 @@ pub async fn get_user(
 
     Ok(Json(users))
 }
+
+pub async fn get_home_currency(
+    State(state): State<AppState>,
+    Extension((user_id, _role)): Extension<(String, String)>,
+) -> Result<Json<HomeCurrencyResponse>, AppError> {
+    let home_currency =
+        sqlx::query_scalar!("SELECT home_currency FROM users WHERE user_id = ?", user_id)
+            .fetch_optional(&state.db)
+            .await
+            .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+            .ok_or_else(|| AppError(StatusCode::NOT_FOUND, "User not found".to_string()))?;
+
+    Ok(Json(HomeCurrencyResponse { home_currency }))
+}
+
+pub async fn set_home_currency(
+    State(state): State<AppState>,
+    Extension((user_id, _role)): Extension<(String, String)>,
+    Json(payload): Json<SetHomeCurrencyPayload>,
+) -> Result<Json<HomeCurrencyResponse>, AppError> {
+    let home_currency = currency_code_or_400("currency", &payload.currency)
+        .map_err(|e| AppError(e.0, e.1))?;
+
+    let result = sqlx::query!(
+        "UPDATE users SET home_currency = ? WHERE user_id = ?",
+        home_currency,
+        user_id
+    )
+    .execute(&state.db)
+    .await
+    .map_err(|e| {
+        error!("Error updating home currency {}", e);
+        AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+    })?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError(
+            StatusCode::NOT_FOUND,
+            "User not found".to_string(),
+        ));
+    }
+
+    Ok(Json(HomeCurrencyResponse { home_currency }))
+}
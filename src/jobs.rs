@@ -0,0 +1,52 @@
+use std::time::Duration;
+
+use sqlx::SqlitePool;
+use tracing::{error, info};
+
+use crate::handlers::{card, idempotency, statement};
+
+const TRASH_PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+const STATEMENT_CLOSING_INTERVAL: Duration = Duration::from_secs(60 * 60);
+const IDEMPOTENCY_PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+pub fn spawn_trash_purge(db: SqlitePool, retention_days: i64) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(TRASH_PURGE_INTERVAL);
+        loop {
+            interval.tick().await;
+            match card::purge_expired_cards(&db, retention_days).await {
+                Ok(0) => {}
+                Ok(purged) => info!("Purged {} expired cards from trash", purged),
+                Err(e) => error!("Error purging expired cards {}", e),
+            }
+        }
+    });
+}
+
+pub fn spawn_statement_closing(db: SqlitePool) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(STATEMENT_CLOSING_INTERVAL);
+        loop {
+            interval.tick().await;
+            match statement::close_due_statements(&db).await {
+                Ok(0) => {}
+                Ok(closed) => info!("Closed {} card statements", closed),
+                Err(e) => error!("Error closing card statements {}", e),
+            }
+        }
+    });
+}
+
+pub fn spawn_idempotency_purge(db: SqlitePool) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(IDEMPOTENCY_PURGE_INTERVAL);
+        loop {
+            interval.tick().await;
+            match idempotency::purge_expired_keys(&db).await {
+                Ok(0) => {}
+                Ok(purged) => info!("Purged {} expired idempotency keys", purged),
+                Err(e) => error!("Error purging expired idempotency keys {}", e),
+            }
+        }
+    });
+}
@@ -6,10 +6,16 @@ use std::sync::Arc;
 use tracing::{error, info};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 mod app;
+mod cli;
+mod extractors;
 mod handlers;
+mod jobs;
 mod middleware;
 mod models;
+mod money;
 mod routes;
+#[cfg(test)]
+mod tests;
 
 pub mod proto {
     include!(concat!(env!("OUT_DIR"), "/flinderax_backend.rs"));
@@ -37,6 +43,11 @@ async fn main() -> Result<(), sqlx::Error> {
     sqlx::migrate!().run(&pool).await?;
     info!("Database connected successfully");
 
+    let args: Vec<String> = env::args().skip(1).collect();
+    if !args.is_empty() {
+        return cli::run(&pool, &args).await;
+    }
+
     info!("Running migrations");
     info!("Initializing Redis...");
     let redis_url = env::var("REDIS_URL").unwrap_or_else(|_| "redis://127.0.0.1/".to_string());
@@ -73,12 +84,31 @@ async fn main() -> Result<(), sqlx::Error> {
         rusty_paseto::core::Local,
     >::from(rusty_paseto::prelude::Key::from(key_bytes.as_slice())));
 
+    let trash_retention_days = env::var("CARD_TRASH_RETENTION_DAYS")
+        .ok()
+        .and_then(|v| v.parse::<i64>().ok())
+        .unwrap_or(30);
+
+    let max_total_due = match env::var("MAX_TOTAL_DUE") {
+        Ok(v) => money::Money::parse(&v)
+            .ok()
+            .filter(|v| v.is_positive())
+            .expect("MAX_TOTAL_DUE must be a positive amount"),
+        Err(_) => money::DEFAULT_MAX_TOTAL_DUE,
+    };
+
     let state = models::AppState {
         db: pool.clone(),
         redis: redis_manager,
         paseto_key,
+        trash_retention_days,
+        max_total_due,
     };
 
+    jobs::spawn_trash_purge(pool.clone(), trash_retention_days);
+    jobs::spawn_statement_closing(pool.clone());
+    jobs::spawn_idempotency_purge(pool.clone());
+
     let app = app::build_router(state);
     info!("Running Server!");
 
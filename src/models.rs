@@ -1,15 +1,25 @@
-use std::sync::Arc;
 use serde::{Deserialize, Serialize};
+use std::sync::Arc;
 
 use redis::aio::ConnectionManager;
 use sqlx::FromRow;
 use sqlx::SqlitePool;
 
+use crate::money::Money;
+
 #[derive(Clone)]
 pub struct AppState {
     pub db: SqlitePool,
     pub redis: Option<ConnectionManager>,
-    pub paseto_key: Arc<rusty_paseto::prelude::PasetoSymmetricKey<rusty_paseto::core::V4, rusty_paseto::core::Local>>,
+    pub paseto_key: Arc<
+        rusty_paseto::prelude::PasetoSymmetricKey<
+            rusty_paseto::core::V4,
+            rusty_paseto::core::Local,
+        >,
+    >,
+    pub trash_retention_days: i64,
+    /// Largest total due `insert_transaction` accepts.
+    pub max_total_due: Money,
 }
 
 #[derive(serde::Deserialize, Clone)]
@@ -53,8 +63,20 @@ pub struct LoginResponse {
 pub struct CreateCardPayload {
     pub card_name: String,
     pub card_bank: String,
-    pub card_primary_color: (u8, u8, u8),
-    pub card_secondary_color: (u8, u8, u8),
+    /// Defaults to the issuer's colors when `card_bank` is in the catalog.
+    pub card_primary_color: Option<(u8, u8, u8)>,
+    pub card_secondary_color: Option<(u8, u8, u8)>,
+    pub credit_limit: Option<Money>,
+    pub statement_day: Option<u8>,
+    pub payment_due_day: Option<u8>,
+    pub payment_due_offset_days: Option<u8>,
+    pub network: Option<CardNetwork>,
+    pub last_four: Option<String>,
+    pub expiry_month: Option<u8>,
+    pub expiry_year: Option<u16>,
+    pub currency: Option<String>,
+    /// Takes precedence over `card_primary_color` / `card_secondary_color`.
+    pub theme: Option<CardThemeInput>,
 }
 #[derive(Serialize)]
 pub struct CardResponse {
@@ -68,17 +90,318 @@ pub struct DeleteCardPayload {
 }
 
 #[derive(Deserialize)]
-pub struct UpdateCardPayload {
+pub struct RestoreCardPayload {
+    pub card_id: String,
+}
+
+#[derive(Deserialize)]
+pub struct PurgeCardPayload {
+    pub card_id: String,
+}
+
+#[derive(Serialize)]
+pub struct TrashedCardResponse {
     pub card_id: String,
     pub card_name: String,
     pub card_bank: String,
     pub card_primary_color: (u8, u8, u8),
     pub card_secondary_color: (u8, u8, u8),
+    pub deleted_at: String,
+    pub purge_at: String,
+}
+
+#[derive(Deserialize)]
+pub struct ArchiveCardPayload {
+    pub card_id: String,
+}
+
+#[derive(Deserialize)]
+pub struct GetAllCardsQuery {
+    #[serde(default)]
+    pub include_archived: bool,
+    /// Only return cards carrying this tag id.
+    pub tag: Option<String>,
+    /// Case-insensitive substring of the card name or bank.
+    pub q: Option<String>,
+    pub min_due: Option<Money>,
+    pub max_due: Option<Money>,
+    /// Only cards whose balance changed at or after this time (RFC 3339 or `YYYY-MM-DD`).
+    pub updated_since: Option<String>,
+    #[serde(default)]
+    pub sort: CardSort,
+    #[serde(default)]
+    pub order: SortOrder,
+    pub limit: Option<u32>,
+    /// `next_cursor` from the previous page.
+    pub cursor: Option<String>,
+    pub status: Option<CardStatus>,
+    /// Show balances as they stood at this time (RFC 3339 or `YYYY-MM-DD`).
+    pub as_of: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum CardSort {
+    /// Pinned cards first, then the user's own order.
+    #[default]
+    Position,
+    Due,
+    Delta,
+    Name,
+    Updated,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+impl CardSort {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CardSort::Position => "position",
+            CardSort::Due => "due",
+            CardSort::Delta => "delta",
+            CardSort::Name => "name",
+            CardSort::Updated => "updated",
+        }
+    }
+}
+
+impl SortOrder {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SortOrder::Asc => "asc",
+            SortOrder::Desc => "desc",
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ReorderCardsPayload {
+    pub card_ids: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct ReorderCardsResponse {
+    pub card_ids: Vec<String>,
+    pub status: bool,
+}
+
+#[derive(Deserialize, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchMode {
+    /// Any failed operation rolls back the whole batch.
+    #[default]
+    AllOrNothing,
+    /// Failed operations are rolled back on their own; the rest are kept.
+    BestEffort,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum CardOperation {
+    Create(CreateCardPayload),
+    Update(UpdateCardPayload),
+    Delete(DeleteCardPayload),
+}
+
+impl CardOperation {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CardOperation::Create(_) => "create",
+            CardOperation::Update(_) => "update",
+            CardOperation::Delete(_) => "delete",
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct CardBatchPayload {
+    #[serde(default)]
+    pub mode: BatchMode,
+    pub operations: Vec<CardOperation>,
+}
+
+#[derive(Serialize)]
+pub struct CardOperationResult {
+    pub index: usize,
+    pub op: &'static str,
+    /// The card created, updated or deleted; `None` for a create that failed.
+    pub card_id: Option<String>,
+    /// HTTP status the operation would have returned on its own.
+    pub status: u16,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct CardBatchResponse {
+    /// False when an all-or-nothing batch was rolled back; successful results
+    /// then describe what would have happened.
+    pub committed: bool,
+    pub succeeded: usize,
+    pub failed: usize,
+    pub results: Vec<CardOperationResult>,
+}
+
+#[derive(Deserialize)]
+pub struct PinCardPayload {
+    pub card_id: String,
+    pub pinned: bool,
+}
+
+/// Fields left out keep their value. The optional card details are
+/// `Option<Option<_>>` so that an explicit `null` clears them.
+#[derive(Deserialize)]
+pub struct UpdateCardPayload {
+    pub card_id: String,
+    pub card_name: Option<String>,
+    pub card_bank: Option<String>,
+    pub card_primary_color: Option<(u8, u8, u8)>,
+    pub card_secondary_color: Option<(u8, u8, u8)>,
+    #[serde(default, with = "::serde_with::rust::double_option")]
+    pub credit_limit: Option<Option<Money>>,
+    #[serde(default, with = "::serde_with::rust::double_option")]
+    pub statement_day: Option<Option<u8>>,
+    #[serde(default, with = "::serde_with::rust::double_option")]
+    pub payment_due_day: Option<Option<u8>>,
+    #[serde(default, with = "::serde_with::rust::double_option")]
+    pub payment_due_offset_days: Option<Option<u8>>,
+    #[serde(default, with = "::serde_with::rust::double_option")]
+    pub network: Option<Option<CardNetwork>>,
+    #[serde(default, with = "::serde_with::rust::double_option")]
+    pub last_four: Option<Option<String>>,
+    #[serde(default, with = "::serde_with::rust::double_option")]
+    pub expiry_month: Option<Option<u8>>,
+    #[serde(default, with = "::serde_with::rust::double_option")]
+    pub expiry_year: Option<Option<u16>>,
+    pub currency: Option<String>,
+    pub theme: Option<CardThemeInput>,
+}
+
+/// How a card is drawn. Colors are `#rrggbb`, or `#rrggbbaa` when translucent.
+#[derive(Serialize, Deserialize)]
+pub struct CardTheme {
+    pub primary_color: String,
+    pub secondary_color: String,
+    /// Gradient direction in degrees, as in CSS `linear-gradient`.
+    pub gradient_angle: u16,
+    /// `#ffffff` or `#000000`, whichever reads better over both colors.
+    pub text_color: String,
+    /// Set while the card still matches a named preset exactly.
+    pub preset: Option<String>,
+}
+
+/// Changes to a card's theme. `preset` is applied first and the other fields
+/// override it; anything left out keeps its current value.
+#[derive(Deserialize)]
+pub struct CardThemeInput {
+    pub preset: Option<String>,
+    pub primary_color: Option<String>,
+    pub secondary_color: Option<String>,
+    pub gradient_angle: Option<u16>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum CardNetwork {
+    Visa,
+    Mastercard,
+    Rupay,
+    Amex,
+}
+
+impl CardNetwork {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CardNetwork::Visa => "visa",
+            CardNetwork::Mastercard => "mastercard",
+            CardNetwork::Rupay => "rupay",
+            CardNetwork::Amex => "amex",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum CardStatus {
+    Active,
+    Frozen,
+    Lost,
+    Closed,
+}
+
+impl CardStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CardStatus::Active => "active",
+            CardStatus::Frozen => "frozen",
+            CardStatus::Lost => "lost",
+            CardStatus::Closed => "closed",
+        }
+    }
+
+    pub fn parse(status: &str) -> Option<Self> {
+        match status {
+            "active" => Some(CardStatus::Active),
+            "frozen" => Some(CardStatus::Frozen),
+            "lost" => Some(CardStatus::Lost),
+            "closed" => Some(CardStatus::Closed),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct SetCardStatusPayload {
+    pub card_id: String,
+    pub status: CardStatus,
+}
+
+#[derive(Deserialize)]
+pub struct ReplaceCardPayload {
+    /// The card being replaced.
+    pub card_id: String,
+    pub replacement_card_id: String,
+    /// Carry the old card's running balance over to the replacement, which must
+    /// not have any history of its own yet. Defaults to true.
+    pub inherit_balance: Option<bool>,
+}
+
+#[derive(Deserialize)]
+pub struct SetUtilizationThresholdsPayload {
+    pub card_id: String,
+    pub thresholds: Vec<f32>,
+}
+
+#[derive(Serialize)]
+pub struct UtilizationThresholdsResponse {
+    pub card_id: String,
+    pub thresholds: Vec<f32>,
+}
+
+#[derive(Deserialize)]
+pub struct GetCardRevisionsPayload {
+    pub card_id: String,
+}
+
+#[derive(Serialize, FromRow)]
+pub struct CardRevision {
+    pub revision_id: String,
+    pub field_name: String,
+    pub old_value: String,
+    pub new_value: String,
+    pub changed_at: String,
 }
 
 #[derive(Deserialize)]
 pub struct GetCardForUser {
     pub card_id: String,
+    /// Show the card's balance as it stood at this time (RFC 3339 or `YYYY-MM-DD`).
+    pub as_of: Option<String>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -88,35 +411,410 @@ pub struct ShowGetCardResponse {
     pub card_bank: String,
     pub card_primary_color: (u8, u8, u8),
     pub card_secondary_color: (u8, u8, u8),
-    pub last_total_due: Option<f32>,
-    pub last_delta: Option<f32>,
+    pub last_total_due: Option<Money>,
+    pub last_delta: Option<Money>,
+    pub archived: bool,
+    pub credit_limit: Option<Money>,
+    pub utilization_percent: Option<f32>,
+    pub statement_day: Option<u8>,
+    pub payment_due_day: Option<u8>,
+    pub payment_due_offset_days: Option<u8>,
+    pub next_statement_date: Option<String>,
+    pub next_due_date: Option<String>,
+    pub days_until_due: Option<i64>,
+    pub position: i64,
+    pub pinned: bool,
+    pub network: Option<String>,
+    pub last_four: Option<String>,
+    pub expiry_month: Option<u8>,
+    pub expiry_year: Option<u16>,
+    pub expiring: bool,
+    pub expired: bool,
+    pub currency: String,
+    pub tags: Vec<Tag>,
+    /// When the balance last changed; starts out as the creation time.
+    pub updated_at: Option<String>,
+    pub issuer_id: Option<String>,
+    pub issuer_logo_path: Option<String>,
+    pub theme: CardTheme,
+    pub status: CardStatus,
+    pub status_changed_at: Option<String>,
+    /// The card that replaced this one.
+    pub replaced_by: Option<String>,
+    /// The card this one replaced.
+    pub replaces: Option<String>,
+    /// Set when the balance was replayed up to `as_of` rather than read live.
+    pub historical: bool,
+    pub as_of: Option<String>,
 }
-#[derive(Deserialize)]
+#[derive(Serialize, Deserialize)]
 pub struct InsertTransactionPayload {
     pub card_id: String,
-    pub amount_due: f32,
+    #[serde(default)]
+    pub event_type: EventType,
+    /// The card's new total due, for `due` events.
+    pub amount_due: Option<Money>,
+    /// The event's own amount, for every other type. Positive, except that an
+    /// adjustment may go either way.
+    pub amount: Option<Money>,
+    /// Records a `due` snapshot lower than the current total as a payment of the
+    /// difference. Without it a lower total is rejected.
+    #[serde(default)]
+    pub payment: bool,
+    /// Where a `purchase` was made; required for purchases.
+    pub merchant: Option<String>,
+    pub category: Option<String>,
 }
 
-#[derive(Serialize)]
+/// What a card event records. Each type moves the total due differently and
+/// has its own effect on the money to move out of the spending account
+/// (`last_delta`).
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug, Default, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(rename_all = "lowercase")]
+pub enum EventType {
+    /// A snapshot of the total due from the bank. New spending is moved out.
+    #[default]
+    Due,
+    /// A bill payment. The money was already set aside, so nothing moves.
+    Payment,
+    /// Money returned by a merchant. It can move back to spending.
+    Refund,
+    /// A fee or interest charge, moved out like new spending.
+    Fee,
+    /// A correction to the total that doesn't move any money.
+    Adjustment,
+    /// A single purchase logged as it happens, moved out straight away. Lets a
+    /// card be tracked without looking up the bank's total due.
+    Purchase,
+}
+
+impl EventType {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            EventType::Due => "due",
+            EventType::Payment => "payment",
+            EventType::Refund => "refund",
+            EventType::Fee => "fee",
+            EventType::Adjustment => "adjustment",
+            EventType::Purchase => "purchase",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
 pub struct InsertTransactionResponse {
     pub transaction_id: String,
-    pub amount_due: f32,
+    /// Money to move out of the spending account for this event; negative when
+    /// it can move back.
+    pub amount_due: Money,
+    pub event_type: EventType,
+    pub utilization_percent: Option<f32>,
+    pub warnings: Vec<UtilizationWarning>,
     pub status: bool,
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct UtilizationWarning {
+    pub threshold_percent: f32,
+    pub utilization_percent: f32,
+    pub message: String,
+}
+
 #[derive(Deserialize, Serialize, FromRow)]
 pub struct CardTransactionHistory {
     pub transaction_id: String,
-    pub total_due_input: f32,
+    pub card_id: String,
+    /// The total due after this event.
+    pub total_due_input: Money,
     pub timestamp: String,
+    pub event_type: EventType,
+    /// The amount entered for anything other than a `due` snapshot.
+    pub amount: Option<Money>,
+    pub merchant: Option<String>,
+    pub category: Option<String>,
 }
 
 #[derive(Deserialize)]
 pub struct GetHistoryPayload {
     pub card_id: String,
+    /// Also include the history of the cards this one replaced.
+    #[serde(default)]
+    pub include_predecessors: bool,
 }
 
 #[derive(Deserialize)]
 pub struct ResetTransactionsPayload {
     pub card_id: String,
 }
+
+#[derive(Deserialize)]
+pub struct UpdateTransactionPayload {
+    pub card_id: String,
+    pub transaction_id: String,
+    /// The new total due for a `due` snapshot, or the new amount for any other
+    /// type.
+    pub amount: Option<Money>,
+    /// RFC 3339 timestamp or YYYY-MM-DD; moves the event within the timeline.
+    pub timestamp: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct UndoTransactionPayload {
+    pub card_id: String,
+    /// The event the client believes is the card's latest. The undo is refused
+    /// if anything was recorded after it.
+    pub transaction_id: String,
+}
+
+#[derive(Deserialize)]
+pub struct DeleteTransactionPayload {
+    pub card_id: String,
+    pub transaction_id: String,
+}
+
+#[derive(Deserialize)]
+pub struct RebuildRunningStatePayload {
+    /// Only this card; every card when absent.
+    pub card_id: Option<String>,
+    /// Report without writing anything. Defaults to true.
+    pub dry_run: Option<bool>,
+}
+
+/// A card whose stored running state doesn't match a replay of its events.
+#[derive(Serialize)]
+pub struct RunningStateMismatch {
+    pub card_id: String,
+    pub user_id: String,
+    /// `None` when the card has no running-state row.
+    pub stored_total_due: Option<Money>,
+    pub stored_delta: Option<Money>,
+    pub replayed_total_due: Money,
+    pub replayed_delta: Money,
+    /// Events whose recorded total due disagrees with the replay.
+    pub stale_events: usize,
+    pub repaired: bool,
+}
+
+#[derive(Serialize)]
+pub struct RebuildRunningStateResponse {
+    pub dry_run: bool,
+    pub checked: usize,
+    pub mismatches: Vec<RunningStateMismatch>,
+}
+
+/// A card's running state after its events were replayed.
+#[derive(Serialize)]
+pub struct RunningStateResponse {
+    pub card_id: String,
+    pub last_total_due: Money,
+    pub last_delta: Money,
+}
+
+#[derive(Deserialize)]
+pub struct CloseStatementPayload {
+    pub card_id: String,
+}
+
+#[derive(Deserialize)]
+pub struct GetStatementsPayload {
+    pub card_id: String,
+    /// Also include statements of the cards this one replaced.
+    #[serde(default)]
+    pub include_predecessors: bool,
+}
+
+#[derive(Deserialize)]
+pub struct GetStatementPayload {
+    pub card_id: String,
+    pub statement_id: String,
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum PaymentStatus {
+    Unpaid,
+    PartiallyPaid,
+    Paid,
+}
+
+impl PaymentStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            PaymentStatus::Unpaid => "unpaid",
+            PaymentStatus::PartiallyPaid => "partially_paid",
+            PaymentStatus::Paid => "paid",
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct UpdateStatementPaymentPayload {
+    pub card_id: String,
+    pub statement_id: String,
+    pub payment_status: PaymentStatus,
+    pub amount_paid: Option<Money>,
+}
+
+#[derive(Serialize, FromRow)]
+pub struct CardStatement {
+    pub statement_id: String,
+    pub card_id: String,
+    pub cycle_start: String,
+    pub cycle_end: String,
+    pub closing_total_due: Money,
+    pub closing_delta: Money,
+    pub due_date: Option<String>,
+    pub payment_status: String,
+    pub amount_paid: Money,
+    pub paid_at: Option<String>,
+    pub closed_at: String,
+}
+
+#[derive(Serialize)]
+pub struct CardStatementDetail {
+    #[serde(flatten)]
+    pub statement: CardStatement,
+    pub events: Vec<CardTransactionHistory>,
+}
+
+#[derive(Deserialize)]
+pub struct SetHomeCurrencyPayload {
+    pub currency: String,
+}
+
+#[derive(Serialize)]
+pub struct HomeCurrencyResponse {
+    pub home_currency: String,
+}
+
+#[derive(Serialize, FromRow)]
+pub struct FxRate {
+    pub base_currency: String,
+    pub quote_currency: String,
+    pub rate: f64,
+    pub as_of: String,
+    pub updated_at: String,
+}
+
+#[derive(Deserialize)]
+pub struct SetFxRatePayload {
+    pub base_currency: String,
+    pub quote_currency: String,
+    pub rate: f64,
+    /// `YYYY-MM-DD`; defaults to today.
+    pub as_of: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct DeleteFxRatePayload {
+    pub base_currency: String,
+    pub quote_currency: String,
+}
+
+#[derive(Serialize)]
+pub struct CurrencySubtotal {
+    pub currency: String,
+    pub total_due: Money,
+    /// Units of the home currency per unit of `currency`; `None` when no rate is on file.
+    pub rate: Option<f64>,
+    pub rate_as_of: Option<String>,
+    pub converted_total_due: Option<Money>,
+}
+
+#[derive(Serialize)]
+pub struct HomeCurrencyTotalResponse {
+    pub home_currency: String,
+    /// Sum of every subtotal that could be converted.
+    pub total_due: Money,
+    /// False when at least one currency had no rate and was left out of `total_due`.
+    pub complete: bool,
+    pub currencies: Vec<CurrencySubtotal>,
+}
+
+#[derive(Serialize, Deserialize, FromRow, Clone)]
+pub struct Tag {
+    pub tag_id: String,
+    pub name: String,
+}
+
+#[derive(Deserialize)]
+pub struct CreateTagPayload {
+    pub name: String,
+}
+
+#[derive(Deserialize)]
+pub struct UpdateTagPayload {
+    pub tag_id: String,
+    pub name: String,
+}
+
+#[derive(Deserialize)]
+pub struct DeleteTagPayload {
+    pub tag_id: String,
+}
+
+#[derive(Deserialize)]
+pub struct SetCardTagsPayload {
+    pub card_id: String,
+    pub tag_ids: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct TagCurrencyTotal {
+    pub currency: String,
+    pub last_total_due: Money,
+    pub last_delta: Money,
+}
+
+#[derive(Serialize)]
+pub struct TagSummary {
+    pub tag_id: String,
+    pub name: String,
+    pub card_count: i64,
+    /// Totals over the tag's active cards, one entry per card currency.
+    pub totals: Vec<TagCurrencyTotal>,
+}
+
+#[derive(Serialize)]
+pub struct Issuer {
+    pub issuer_id: String,
+    pub name: String,
+    /// Other spellings that resolve to this issuer, not including `name`.
+    pub aliases: Vec<String>,
+    pub primary_color: (u8, u8, u8),
+    pub secondary_color: (u8, u8, u8),
+    pub logo_path: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct IssuerSearchQuery {
+    pub q: Option<String>,
+    pub limit: Option<u32>,
+}
+
+#[derive(Deserialize)]
+pub struct CreateIssuerPayload {
+    pub name: String,
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    pub primary_color: (u8, u8, u8),
+    pub secondary_color: (u8, u8, u8),
+    pub logo_path: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct UpdateIssuerPayload {
+    pub issuer_id: String,
+    pub name: Option<String>,
+    /// Replaces the full alias list when present.
+    pub aliases: Option<Vec<String>>,
+    pub primary_color: Option<(u8, u8, u8)>,
+    pub secondary_color: Option<(u8, u8, u8)>,
+    pub logo_path: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct DeleteIssuerPayload {
+    pub issuer_id: String,
+}
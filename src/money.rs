@@ -0,0 +1,306 @@
+use std::{
+    fmt,
+    iter::Sum,
+    ops::{Add, Neg, Sub},
+};
+
+use serde::{de, ser, Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::value::RawValue;
+
+/// Minor units per major unit. Every currency is stored at this scale, so a
+/// card can change currency without its history being rescaled. That limits
+/// cards to currencies with two decimal places; see
+/// `currency::currency_code_or_400`.
+pub const MINOR_PER_MAJOR: i64 = 100;
+
+/// Largest amount accepted from clients, in major units. Keeps every sum and
+/// difference we compute far away from `i64` overflow.
+pub const MAX_MAJOR: i64 = 1_000_000_000_000;
+
+/// Default ceiling for a card's total due; override with `MAX_TOTAL_DUE`.
+pub const DEFAULT_MAX_TOTAL_DUE: Money = Money(100_000_000 * MINOR_PER_MAJOR);
+
+/// An amount of money in minor units (hundredths of the card's currency).
+///
+/// Stored as an `INTEGER` column and serialized to JSON as an exact decimal
+/// number (`12345.67`, `0.10`), never through a float. Deserializing also accepts a
+/// decimal string, which avoids float parsing altogether.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, sqlx::Type)]
+#[sqlx(transparent)]
+pub struct Money(i64);
+
+#[derive(Debug, PartialEq)]
+pub enum MoneyError {
+    NotFinite,
+    TooPrecise,
+    OutOfRange,
+    Malformed,
+}
+
+impl fmt::Display for MoneyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self {
+            MoneyError::NotFinite => "amount must be a finite number",
+            MoneyError::TooPrecise => "amount can't have more than two decimal places",
+            MoneyError::OutOfRange => "amount is too large",
+            MoneyError::Malformed => "amount must be a decimal number",
+        };
+        f.write_str(message)
+    }
+}
+
+impl Money {
+    pub const ZERO: Money = Money(0);
+
+    pub const fn from_minor(minor: i64) -> Self {
+        Money(minor)
+    }
+
+    pub const fn minor(self) -> i64 {
+        self.0
+    }
+
+    /// Converts a decimal amount such as `12345.67`. Rejects anything finer
+    /// than a minor unit instead of rounding it away.
+    pub fn from_major(major: f64) -> Result<Self, MoneyError> {
+        if !major.is_finite() {
+            return Err(MoneyError::NotFinite);
+        }
+        if major.abs() > MAX_MAJOR as f64 {
+            return Err(MoneyError::OutOfRange);
+        }
+        let scaled = major * MINOR_PER_MAJOR as f64;
+        let minor = scaled.round();
+        // Decimal inputs rarely land exactly on a float; allow that noise, but
+        // not a genuine third decimal place. The noise grows with the amount,
+        // so the allowance does too.
+        let noise = (scaled.abs() * f64::EPSILON * 4.0).max(1e-6);
+        if (scaled - minor).abs() > noise {
+            return Err(MoneyError::TooPrecise);
+        }
+        Ok(Money(minor as i64))
+    }
+
+    /// Parses a plain decimal string (`-12345.6`, `0.07`) without going through
+    /// a float.
+    pub fn parse(value: &str) -> Result<Self, MoneyError> {
+        let value = value.trim();
+        let (negative, digits) = match value.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, value.strip_prefix('+').unwrap_or(value)),
+        };
+        let (whole, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+        if (whole.is_empty() && fraction.is_empty())
+            || !whole.bytes().all(|b| b.is_ascii_digit())
+            || !fraction.bytes().all(|b| b.is_ascii_digit())
+        {
+            return Err(MoneyError::Malformed);
+        }
+        if fraction.len() > 2 {
+            return Err(MoneyError::TooPrecise);
+        }
+        let whole: i64 = if whole.is_empty() {
+            0
+        } else {
+            whole.parse().map_err(|_| MoneyError::OutOfRange)?
+        };
+        if whole > MAX_MAJOR {
+            return Err(MoneyError::OutOfRange);
+        }
+        let fraction = format!("{:0<2}", fraction)
+            .parse::<i64>()
+            .map_err(|_| MoneyError::Malformed)?;
+        let minor = whole * MINOR_PER_MAJOR + fraction;
+        Ok(Money(if negative { -minor } else { minor }))
+    }
+
+    /// The amount in major units, for percentages, FX conversion and the legacy
+    /// float fields.
+    pub fn to_major(self) -> f64 {
+        self.0 as f64 / MINOR_PER_MAJOR as f64
+    }
+
+    /// Multiplies by `factor` (an exchange rate, say), rounding half away from
+    /// zero to the nearest minor unit.
+    pub fn scale(self, factor: f64) -> Self {
+        Money((self.0 as f64 * factor).round() as i64)
+    }
+
+    pub fn is_positive(self) -> bool {
+        self.0 > 0
+    }
+
+    pub fn is_negative(self) -> bool {
+        self.0 < 0
+    }
+}
+
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.0 < 0 { "-" } else { "" };
+        let minor = self.0.unsigned_abs();
+        let scale = MINOR_PER_MAJOR as u64;
+        write!(f, "{}{}.{:02}", sign, minor / scale, minor % scale)
+    }
+}
+
+impl Add for Money {
+    type Output = Money;
+
+    fn add(self, other: Money) -> Money {
+        Money(self.0 + other.0)
+    }
+}
+
+impl Sub for Money {
+    type Output = Money;
+
+    fn sub(self, other: Money) -> Money {
+        Money(self.0 - other.0)
+    }
+}
+
+impl Neg for Money {
+    type Output = Money;
+
+    fn neg(self) -> Money {
+        Money(-self.0)
+    }
+}
+
+impl Sum for Money {
+    fn sum<I: Iterator<Item = Money>>(iter: I) -> Money {
+        iter.fold(Money::ZERO, Add::add)
+    }
+}
+
+impl Serialize for Money {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        RawValue::from_string(self.to_string())
+            .map_err(ser::Error::custom)?
+            .serialize(serializer)
+    }
+}
+
+struct MoneyVisitor;
+
+impl de::Visitor<'_> for MoneyVisitor {
+    type Value = Money;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a decimal amount")
+    }
+
+    fn visit_f64<E: de::Error>(self, value: f64) -> Result<Money, E> {
+        Money::from_major(value).map_err(E::custom)
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<Money, E> {
+        if value.unsigned_abs() > MAX_MAJOR as u64 {
+            return Err(E::custom(MoneyError::OutOfRange));
+        }
+        Ok(Money(value * MINOR_PER_MAJOR))
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<Money, E> {
+        if value > MAX_MAJOR as u64 {
+            return Err(E::custom(MoneyError::OutOfRange));
+        }
+        Ok(Money(value as i64 * MINOR_PER_MAJOR))
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Money, E> {
+        Money::parse(value).map_err(E::custom)
+    }
+}
+
+impl<'de> Deserialize<'de> for Money {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Money, D::Error> {
+        deserializer.deserialize_any(MoneyVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_reads_plain_decimals_exactly() {
+        assert_eq!(Money::parse("12345.67"), Ok(Money(1_234_567)));
+        assert_eq!(Money::parse("0.07"), Ok(Money(7)));
+        assert_eq!(Money::parse("-12345.6"), Ok(Money(-1_234_560)));
+        assert_eq!(Money::parse("+3"), Ok(Money(300)));
+        assert_eq!(Money::parse(" .5 "), Ok(Money(50)));
+        assert_eq!(Money::parse("7."), Ok(Money(700)));
+        assert_eq!(
+            Money::parse("1000000000000.99"),
+            Ok(Money(100_000_000_000_099))
+        );
+    }
+
+    #[test]
+    fn parse_rejects_what_it_cant_store() {
+        assert_eq!(Money::parse("1.005"), Err(MoneyError::TooPrecise));
+        assert_eq!(Money::parse("1000000000001"), Err(MoneyError::OutOfRange));
+        assert_eq!(
+            Money::parse("99999999999999999999"),
+            Err(MoneyError::OutOfRange)
+        );
+        for malformed in ["", ".", "-", "1e5", "12,50", "1.2.3", "--1", "abc", "NaN"] {
+            assert_eq!(
+                Money::parse(malformed),
+                Err(MoneyError::Malformed),
+                "{}",
+                malformed
+            );
+        }
+    }
+
+    #[test]
+    fn from_major_allows_float_noise_but_not_a_third_decimal() {
+        assert_eq!(Money::from_major(19.99), Ok(Money(1999)));
+        assert_eq!(Money::from_major(0.1 + 0.2), Ok(Money(30)));
+        assert_eq!(Money::from_major(1.1 * 3.0), Ok(Money(330)));
+        assert_eq!(Money::from_major(-0.07), Ok(Money(-7)));
+        assert_eq!(
+            Money::from_major(999_999_999_999.99),
+            Ok(Money(99_999_999_999_999))
+        );
+
+        assert_eq!(Money::from_major(1.005), Err(MoneyError::TooPrecise));
+        assert_eq!(Money::from_major(0.001), Err(MoneyError::TooPrecise));
+        assert_eq!(Money::from_major(12_345.678), Err(MoneyError::TooPrecise));
+
+        assert_eq!(Money::from_major(f64::NAN), Err(MoneyError::NotFinite));
+        assert_eq!(Money::from_major(f64::INFINITY), Err(MoneyError::NotFinite));
+        assert_eq!(Money::from_major(1e13), Err(MoneyError::OutOfRange));
+    }
+
+    #[test]
+    fn display_always_shows_two_decimals() {
+        assert_eq!(Money(1_234_567).to_string(), "12345.67");
+        assert_eq!(Money(10).to_string(), "0.10");
+        assert_eq!(Money(-5).to_string(), "-0.05");
+        assert_eq!(Money(-1_200).to_string(), "-12.00");
+        assert_eq!(Money::ZERO.to_string(), "0.00");
+    }
+
+    #[test]
+    fn json_round_trips_exactly() {
+        for minor in [0, 10, -5, 1_234_567, 99_999_999_999_999] {
+            let json = serde_json::to_string(&Money(minor)).unwrap();
+            assert_eq!(json, Money(minor).to_string());
+            assert_eq!(serde_json::from_str::<Money>(&json).unwrap(), Money(minor));
+        }
+        assert_eq!(
+            serde_json::to_string(&[Money(30), Money(-1)]).unwrap(),
+            "[0.30,-0.01]"
+        );
+        assert_eq!(
+            serde_json::from_str::<Money>("\"0.30\"").unwrap(),
+            Money(30)
+        );
+        assert_eq!(serde_json::from_str::<Money>("12").unwrap(), Money(1200));
+        assert!(serde_json::from_str::<Money>("1.005").is_err());
+    }
+}
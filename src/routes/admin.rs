@@ -0,0 +1,11 @@
+use axum::{middleware, routing::post, Router};
+use crate::models::AppState;
+use crate::app::token_validator_auth_middleware;
+use crate::handlers::card_event;
+
+pub fn routes(state: AppState) -> Router {
+    Router::new()
+        .route("/running_state/rebuild", post(card_event::rebuild_running_state))
+        .layer(middleware::from_fn_with_state(state.clone(), token_validator_auth_middleware))
+        .with_state(state)
+}
@@ -1,16 +1,43 @@
 use axum::{routing::{get, post}, Router};
 use crate::models::AppState;
-use crate::handlers::card;
+use crate::handlers::{card, card_batch, card_event, currency, lifecycle, statement, tag, theme};
 
 pub fn routes(state: AppState) -> Router {
     Router::new()
         .route("/create", post(card::create_card))
-        .route("/update", post(card::update))
+        .route("/update", post(card::update).patch(card::update))
+        .route("/revisions", post(card::get_revisions))
         .route("/delete", post(card::delete_card))
+        .route("/batch", post(card_batch::batch_cards))
+        .route("/reorder", post(card::reorder_cards))
+        .route("/pin", post(card::pin_card))
+        .route("/archive", post(card::archive_card))
+        .route("/unarchive", post(card::unarchive_card))
+        .route("/status", post(lifecycle::set_card_status))
+        .route("/replace", post(lifecycle::replace_card))
+        .route("/trash", get(card::get_trash))
+        .route("/restore", post(card::restore_card))
+        .route("/purge", post(card::purge_card))
         .route("/get_card", post(card::get_card))
         .route("/get_all_cards", get(card::get_all_cards))
+        .route("/total", get(currency::get_home_currency_total))
         .route("/insert_transaction", post(card::insert_transaction))
+        .route("/update_transaction", post(card_event::update_transaction))
+        .route("/delete_transaction", post(card_event::delete_transaction))
+        .route("/undo_transaction", post(card_event::undo_transaction))
+        .route("/utilization_thresholds", post(card::set_utilization_thresholds))
+        .route("/get_utilization_thresholds", post(card::get_utilization_thresholds))
         .route("/history", post(card::get_history))
         .route("/reset", post(card::reset_transactions))
+        .route("/statements", post(statement::get_statements))
+        .route("/statements/get", post(statement::get_statement))
+        .route("/statements/close", post(statement::close_statement))
+        .route("/statements/payment", post(statement::update_payment))
+        .route("/tags", get(tag::get_tags))
+        .route("/tags/create", post(tag::create_tag))
+        .route("/tags/update", post(tag::rename_tag))
+        .route("/tags/delete", post(tag::delete_tag))
+        .route("/tags/assign", post(tag::set_card_tags))
+        .route("/themes", get(theme::get_presets))
         .with_state(state)
 }
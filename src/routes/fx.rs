@@ -0,0 +1,17 @@
+use axum::{
+    middleware,
+    routing::{get, post},
+    Router,
+};
+use crate::models::AppState;
+use crate::app::token_validator_auth_middleware;
+use crate::handlers::currency;
+
+pub fn routes(state: AppState) -> Router {
+    Router::new()
+        .route("/rates/set", post(currency::set_rate))
+        .route("/rates/delete", post(currency::remove_rate))
+        .layer(middleware::from_fn_with_state(state.clone(), token_validator_auth_middleware))
+        .route("/rates", get(currency::get_rates))
+        .with_state(state)
+}
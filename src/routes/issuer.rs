@@ -0,0 +1,18 @@
+use axum::{
+    middleware,
+    routing::{get, post},
+    Router,
+};
+use crate::models::AppState;
+use crate::app::token_validator_auth_middleware;
+use crate::handlers::issuer;
+
+pub fn routes(state: AppState) -> Router {
+    Router::new()
+        .route("/create", post(issuer::create_issuer))
+        .route("/update", post(issuer::update_issuer))
+        .route("/delete", post(issuer::delete_issuer))
+        .layer(middleware::from_fn_with_state(state.clone(), token_validator_auth_middleware))
+        .route("/", get(issuer::get_issuers))
+        .with_state(state)
+}
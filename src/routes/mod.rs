@@ -1,3 +1,6 @@
+pub mod admin;
 pub mod card;
 pub mod common;
+pub mod fx;
+pub mod issuer;
 pub mod user;
@@ -12,5 +12,9 @@ pub fn routes(state: AppState) -> Router {
         .route("/delete", post(user::delete))
         .layer(middleware::from_fn_with_state(state.clone(), token_validator_auth_middleware))
         .route("/get", get(user::get_user))
+        .route(
+            "/home_currency",
+            get(user::get_home_currency).post(user::set_home_currency),
+        )
         .with_state(state)
 }
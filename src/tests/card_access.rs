@@ -0,0 +1,346 @@
+//! Another user's cards, transactions, statements and tags must look exactly
+//! like ones that don't exist.
+
+use axum::http::{Method, StatusCode};
+use serde_json::{json, Value};
+
+use super::TestApp;
+
+struct Fixture {
+    app: TestApp,
+    owner: String,
+    intruder: String,
+    /// The intruder's own card, for routes that take a second id.
+    intruder_card: String,
+    card_id: String,
+    trashed_card_id: String,
+    transaction_id: String,
+    statement_id: String,
+    tag_id: String,
+}
+
+async fn fixture() -> Fixture {
+    let app = TestApp::new().await;
+    let owner = app.user("owner").await;
+    let intruder = app.user("intruder").await;
+
+    let card_id = app.card(&owner, "Owner card").await;
+    let trashed_card_id = app.card(&owner, "Trashed card").await;
+    let intruder_card = app.card(&intruder, "Intruder card").await;
+
+    let (status, body) = app
+        .post(
+            "/card/insert_transaction",
+            &owner,
+            json!({ "card_id": card_id, "amount_due": 120 }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let transaction_id = body["transaction_id"].as_str().unwrap().to_string();
+
+    let (status, body) = app
+        .post(
+            "/card/statements/close",
+            &owner,
+            json!({ "card_id": card_id }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let statement_id = body["statement_id"].as_str().unwrap().to_string();
+
+    let (status, body) = app
+        .post("/card/tags/create", &owner, json!({ "name": "personal" }))
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let tag_id = body["tag_id"].as_str().unwrap().to_string();
+
+    let (status, body) = app
+        .post(
+            "/card/delete",
+            &owner,
+            json!({ "card_id": trashed_card_id }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+
+    Fixture {
+        app,
+        owner,
+        intruder,
+        intruder_card,
+        card_id,
+        trashed_card_id,
+        transaction_id,
+        statement_id,
+        tag_id,
+    }
+}
+
+#[tokio::test]
+async fn other_users_card_routes_return_404() {
+    let f = fixture().await;
+    let card = f.card_id.as_str();
+    let requests: Vec<(Method, &str, Value)> = vec![
+        (Method::POST, "/card/get_card", json!({ "card_id": card })),
+        (
+            Method::POST,
+            "/card/update",
+            json!({ "card_id": card, "card_name": "Mine now" }),
+        ),
+        (
+            Method::PATCH,
+            "/card/update",
+            json!({ "card_id": card, "credit_limit": null }),
+        ),
+        (Method::POST, "/card/revisions", json!({ "card_id": card })),
+        (Method::POST, "/card/delete", json!({ "card_id": card })),
+        (
+            Method::POST,
+            "/card/pin",
+            json!({ "card_id": card, "pinned": true }),
+        ),
+        (Method::POST, "/card/archive", json!({ "card_id": card })),
+        (Method::POST, "/card/unarchive", json!({ "card_id": card })),
+        (Method::POST, "/card/reorder", json!({ "card_ids": [card] })),
+        (
+            Method::POST,
+            "/card/restore",
+            json!({ "card_id": f.trashed_card_id }),
+        ),
+        (
+            Method::POST,
+            "/card/purge",
+            json!({ "card_id": f.trashed_card_id }),
+        ),
+        (
+            Method::POST,
+            "/card/utilization_thresholds",
+            json!({ "card_id": card, "thresholds": [50.0] }),
+        ),
+        (
+            Method::POST,
+            "/card/get_utilization_thresholds",
+            json!({ "card_id": card }),
+        ),
+    ];
+    assert_all_not_found(&f, requests).await;
+
+    // None of it went through.
+    let (status, body) = f
+        .app
+        .post("/card/get_card", &f.owner, json!({ "card_id": card }))
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["card_name"], "Owner card");
+    assert_eq!(body["archived"], false);
+    assert_eq!(body["pinned"], false);
+}
+
+#[tokio::test]
+async fn other_users_transaction_routes_return_404() {
+    let f = fixture().await;
+    let card = f.card_id.as_str();
+    let transaction = f.transaction_id.as_str();
+    let own_card = f.intruder_card.as_str();
+    let requests: Vec<(Method, &str, Value)> = vec![
+        (
+            Method::POST,
+            "/card/insert_transaction",
+            json!({ "card_id": card, "amount_due": 1 }),
+        ),
+        (Method::POST, "/card/history", json!({ "card_id": card })),
+        (Method::POST, "/card/reset", json!({ "card_id": card })),
+        (
+            Method::POST,
+            "/card/update_transaction",
+            json!({ "card_id": card, "transaction_id": transaction, "amount": 1 }),
+        ),
+        (
+            Method::POST,
+            "/card/delete_transaction",
+            json!({ "card_id": card, "transaction_id": transaction }),
+        ),
+        (
+            Method::POST,
+            "/card/undo_transaction",
+            json!({ "card_id": card, "transaction_id": transaction }),
+        ),
+        // The owner's transaction through the intruder's own card.
+        (
+            Method::POST,
+            "/card/update_transaction",
+            json!({ "card_id": own_card, "transaction_id": transaction, "amount": 1 }),
+        ),
+        (
+            Method::POST,
+            "/card/delete_transaction",
+            json!({ "card_id": own_card, "transaction_id": transaction }),
+        ),
+        (
+            Method::POST,
+            "/card/undo_transaction",
+            json!({ "card_id": own_card, "transaction_id": transaction }),
+        ),
+    ];
+    assert_all_not_found(&f, requests).await;
+
+    let (status, body) = f
+        .app
+        .post(
+            "/card/history",
+            &f.intruder,
+            json!({ "card_id": card, "include_predecessors": true }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND, "{}", body);
+}
+
+#[tokio::test]
+async fn other_users_statement_routes_return_404() {
+    let f = fixture().await;
+    let card = f.card_id.as_str();
+    let statement = f.statement_id.as_str();
+    let own_card = f.intruder_card.as_str();
+    let requests: Vec<(Method, &str, Value)> = vec![
+        (Method::POST, "/card/statements", json!({ "card_id": card })),
+        (
+            Method::POST,
+            "/card/statements/get",
+            json!({ "card_id": card, "statement_id": statement }),
+        ),
+        (
+            Method::POST,
+            "/card/statements/close",
+            json!({ "card_id": card }),
+        ),
+        (
+            Method::POST,
+            "/card/statements/payment",
+            json!({ "card_id": card, "statement_id": statement, "payment_status": "paid" }),
+        ),
+        (
+            Method::POST,
+            "/card/statements/get",
+            json!({ "card_id": own_card, "statement_id": statement }),
+        ),
+        (
+            Method::POST,
+            "/card/statements/payment",
+            json!({ "card_id": own_card, "statement_id": statement, "payment_status": "paid" }),
+        ),
+    ];
+    assert_all_not_found(&f, requests).await;
+}
+
+#[tokio::test]
+async fn other_users_tag_routes_return_404() {
+    let f = fixture().await;
+    let card = f.card_id.as_str();
+    let tag = f.tag_id.as_str();
+    let requests: Vec<(Method, &str, Value)> = vec![
+        (
+            Method::POST,
+            "/card/tags/update",
+            json!({ "tag_id": tag, "name": "stolen" }),
+        ),
+        (Method::POST, "/card/tags/delete", json!({ "tag_id": tag })),
+        (
+            Method::POST,
+            "/card/tags/assign",
+            json!({ "card_id": card, "tag_ids": [] }),
+        ),
+        (
+            Method::POST,
+            "/card/tags/assign",
+            json!({ "card_id": f.intruder_card, "tag_ids": [tag] }),
+        ),
+    ];
+    assert_all_not_found(&f, requests).await;
+}
+
+#[tokio::test]
+async fn other_users_lifecycle_routes_return_404() {
+    let f = fixture().await;
+    let card = f.card_id.as_str();
+    let own_card = f.intruder_card.as_str();
+    let requests: Vec<(Method, &str, Value)> = vec![
+        (
+            Method::POST,
+            "/card/status",
+            json!({ "card_id": card, "status": "frozen" }),
+        ),
+        (
+            Method::POST,
+            "/card/replace",
+            json!({ "card_id": card, "replacement_card_id": own_card }),
+        ),
+        (
+            Method::POST,
+            "/card/replace",
+            json!({ "card_id": own_card, "replacement_card_id": card }),
+        ),
+    ];
+    assert_all_not_found(&f, requests).await;
+}
+
+#[tokio::test]
+async fn other_users_cards_fail_inside_a_batch() {
+    let f = fixture().await;
+    let (status, body) = f
+        .app
+        .post(
+            "/card/batch",
+            &f.intruder,
+            json!({
+                "mode": "best_effort",
+                "operations": [
+                    { "op": "update", "card_id": f.card_id, "card_name": "Mine now" },
+                    { "op": "delete", "card_id": f.card_id },
+                ],
+            }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    for result in body["results"].as_array().unwrap() {
+        assert_eq!(result["status"], 404, "{}", body);
+    }
+}
+
+#[tokio::test]
+async fn other_users_cards_are_left_out_of_lists() {
+    let f = fixture().await;
+    for path in [
+        "/card/get_all_cards",
+        "/card/trash",
+        "/card/tags",
+        "/card/total",
+    ] {
+        let (status, body) = f
+            .app
+            .request(Method::GET, path, &f.intruder, &[], None)
+            .await;
+        assert_eq!(status, StatusCode::OK, "{} {}", path, body);
+        let body = body.to_string();
+        for id in [&f.card_id, &f.trashed_card_id, &f.tag_id] {
+            assert!(!body.contains(id.as_str()), "{} leaked {}", path, id);
+        }
+    }
+}
+
+async fn assert_all_not_found(f: &Fixture, requests: Vec<(Method, &str, Value)>) {
+    for (method, path, body) in requests {
+        let (status, response) = f
+            .app
+            .request(method.clone(), path, &f.intruder, &[], Some(body.clone()))
+            .await;
+        assert_eq!(
+            status,
+            StatusCode::NOT_FOUND,
+            "{} {} {} -> {}",
+            method,
+            path,
+            body,
+            response
+        );
+    }
+}
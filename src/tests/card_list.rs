@@ -0,0 +1,211 @@
+//! Sorting and cursor paging of `get_all_cards`, live and point-in-time.
+
+use axum::http::{Method, StatusCode};
+use prost::Message;
+use serde_json::json;
+use time::{format_description::well_known::Rfc3339, Duration, OffsetDateTime};
+
+use super::TestApp;
+use crate::proto::CardList;
+
+const SORTS: [&str; 5] = ["position", "due", "delta", "name", "updated"];
+
+async fn list(app: &TestApp, token: &str, query: &str) -> CardList {
+    let (status, body) = app
+        .send(
+            Method::GET,
+            &format!("/card/get_all_cards?{}", query),
+            token,
+            &[],
+            None,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", String::from_utf8_lossy(&body));
+    CardList::decode(body).unwrap()
+}
+
+/// Every card id, following `next_cursor` one page of `limit` at a time.
+async fn all_pages(app: &TestApp, token: &str, query: &str, limit: u32) -> Vec<String> {
+    let mut ids = Vec::new();
+    let mut cursor: Option<String> = None;
+    loop {
+        let mut page_query = format!("{}&limit={}", query, limit);
+        if let Some(cursor) = &cursor {
+            page_query.push_str(&format!("&cursor={}", cursor));
+        }
+        let page = list(app, token, &page_query).await;
+        assert!(page.cards.len() <= limit as usize);
+        ids.extend(page.cards.into_iter().map(|card| card.card_id));
+        match page.next_cursor {
+            Some(next) => cursor = Some(next),
+            None => return ids,
+        }
+    }
+}
+
+async fn transaction(app: &TestApp, token: &str, body: serde_json::Value) {
+    let (status, body) = app.post("/card/insert_transaction", token, body).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+}
+
+async fn cards(app: &TestApp, token: &str) {
+    let delta = app.card(token, "Delta").await;
+    let alpha = app.card(token, "alpha").await;
+    let charlie = app.card(token, "Charlie").await;
+    app.card(token, "bravo").await;
+    let echo = app.card(token, "Echo").await;
+
+    for (card_id, amount_due) in [(&delta, 300), (&alpha, 100), (&charlie, 300), (&echo, 50)] {
+        transaction(
+            app,
+            token,
+            json!({ "card_id": card_id, "amount_due": amount_due }),
+        )
+        .await;
+    }
+    transaction(
+        app,
+        token,
+        json!({ "card_id": echo, "event_type": "refund", "amount": 20 }),
+    )
+    .await;
+
+    let (status, body) = app
+        .post(
+            "/card/pin",
+            token,
+            json!({ "card_id": charlie, "pinned": true }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+}
+
+#[tokio::test]
+async fn pages_follow_the_full_list_for_every_sort() {
+    let app = TestApp::new().await;
+    let token = app.user("lister").await;
+    cards(&app, &token).await;
+
+    for sort in SORTS {
+        for order in ["asc", "desc"] {
+            let query = format!("sort={}&order={}", sort, order);
+            let full: Vec<String> = list(&app, &token, &query)
+                .await
+                .cards
+                .into_iter()
+                .map(|card| card.card_id)
+                .collect();
+            assert_eq!(full.len(), 5, "{}", query);
+            for limit in [1, 2, 4] {
+                assert_eq!(
+                    all_pages(&app, &token, &query, limit).await,
+                    full,
+                    "{} limit={}",
+                    query,
+                    limit
+                );
+            }
+        }
+    }
+}
+
+#[tokio::test]
+async fn sorts_order_by_the_requested_key() {
+    let app = TestApp::new().await;
+    let token = app.user("lister").await;
+    cards(&app, &token).await;
+
+    let names = |list: CardList| -> Vec<String> {
+        list.cards.into_iter().map(|card| card.card_name).collect()
+    };
+    assert_eq!(
+        names(list(&app, &token, "sort=name").await),
+        ["alpha", "bravo", "Charlie", "Delta", "Echo"]
+    );
+    assert_eq!(
+        names(list(&app, &token, "sort=name&order=desc").await),
+        ["Echo", "Delta", "Charlie", "bravo", "alpha"]
+    );
+    // Pinned first, then the order the cards were created in.
+    assert_eq!(
+        names(list(&app, &token, "sort=position").await),
+        ["Charlie", "Delta", "alpha", "bravo", "Echo"]
+    );
+
+    let dues: Vec<i64> = list(&app, &token, "sort=due&order=desc")
+        .await
+        .cards
+        .into_iter()
+        .map(|card| card.last_total_due_minor.unwrap_or_default())
+        .collect();
+    assert_eq!(dues, [30000, 30000, 10000, 3000, 0]);
+
+    let deltas: Vec<i64> = list(&app, &token, "sort=delta")
+        .await
+        .cards
+        .into_iter()
+        .map(|card| card.last_delta_minor.unwrap_or_default())
+        .collect();
+    assert_eq!(deltas, [-2000, 0, 10000, 30000, 30000]);
+}
+
+#[tokio::test]
+async fn point_in_time_lists_page_like_live_ones() {
+    let app = TestApp::new().await;
+    let token = app.user("lister").await;
+    cards(&app, &token).await;
+
+    // Everything has happened by then, so the replayed list matches the live one.
+    let as_of = (OffsetDateTime::now_utc() + Duration::minutes(1))
+        .format(&Rfc3339)
+        .unwrap()
+        .replace('+', "%2B");
+    for sort in SORTS {
+        for order in ["asc", "desc"] {
+            let query = format!("sort={}&order={}", sort, order);
+            let live: Vec<String> = list(&app, &token, &query)
+                .await
+                .cards
+                .into_iter()
+                .map(|card| card.card_id)
+                .collect();
+            let past_query = format!("{}&as_of={}", query, as_of);
+            let past = list(&app, &token, &past_query).await;
+            assert!(past.cards.iter().all(|card| card.historical));
+            let past: Vec<String> = past.cards.into_iter().map(|card| card.card_id).collect();
+            assert_eq!(past, live, "{}", past_query);
+            assert_eq!(
+                all_pages(&app, &token, &past_query, 2).await,
+                live,
+                "{}",
+                past_query
+            );
+        }
+    }
+
+    // Before anything was recorded every balance was zero.
+    let before = list(&app, &token, "as_of=2000-01-01&min_due=1").await;
+    assert!(before.cards.is_empty());
+}
+
+#[tokio::test]
+async fn cursors_only_resume_the_sort_they_came_from() {
+    let app = TestApp::new().await;
+    let token = app.user("lister").await;
+    cards(&app, &token).await;
+
+    let cursor = list(&app, &token, "sort=due&limit=2")
+        .await
+        .next_cursor
+        .unwrap();
+    let (status, _) = app
+        .send(
+            Method::GET,
+            &format!("/card/get_all_cards?sort=name&limit=2&cursor={}", cursor),
+            &token,
+            &[],
+            None,
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
@@ -0,0 +1,148 @@
+//! Partial card updates and the revisions they leave behind.
+
+use axum::http::{Method, StatusCode};
+use serde_json::{json, Value};
+
+use super::TestApp;
+
+async fn patch(app: &TestApp, token: &str, body: Value) -> Value {
+    let (status, body) = app
+        .request(Method::PATCH, "/card/update", token, &[], Some(body))
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    body
+}
+
+async fn revisions(app: &TestApp, token: &str, card_id: &str) -> Vec<Value> {
+    let (status, body) = app
+        .post("/card/revisions", token, json!({ "card_id": card_id }))
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    body.as_array().unwrap().clone()
+}
+
+#[tokio::test]
+async fn updates_only_the_fields_sent() {
+    let app = TestApp::new().await;
+    let token = app.user("owner").await;
+    let card_id = app.card(&token, "Everyday").await;
+
+    let card = patch(
+        &app,
+        &token,
+        json!({ "card_id": card_id, "card_name": "Groceries" }),
+    )
+    .await;
+    assert_eq!(card["card_name"], "Groceries");
+    assert_eq!(card["card_bank"], "Test Bank");
+    assert_eq!(card["card_primary_color"], json!([10, 20, 30]));
+    assert_eq!(card["card_secondary_color"], json!([40, 50, 60]));
+
+    let revisions = revisions(&app, &token, &card_id).await;
+    assert_eq!(revisions.len(), 1, "{:?}", revisions);
+    assert_eq!(revisions[0]["field_name"], "card_name");
+    assert_eq!(revisions[0]["old_value"], "Everyday");
+    assert_eq!(revisions[0]["new_value"], "Groceries");
+}
+
+#[tokio::test]
+async fn null_clears_an_optional_field() {
+    let app = TestApp::new().await;
+    let token = app.user("owner").await;
+    let card_id = app.card(&token, "Everyday").await;
+
+    let card = patch(
+        &app,
+        &token,
+        json!({ "card_id": card_id, "credit_limit": 500, "statement_day": 5 }),
+    )
+    .await;
+    assert_eq!(card["credit_limit"], 500.0);
+
+    // Left out keeps the value; null clears it.
+    let card = patch(
+        &app,
+        &token,
+        json!({ "card_id": card_id, "credit_limit": null }),
+    )
+    .await;
+    assert!(card["credit_limit"].is_null(), "{}", card);
+    assert_eq!(card["statement_day"], 5);
+
+    let revisions = revisions(&app, &token, &card_id).await;
+    let limits: Vec<(&str, &str)> = revisions
+        .iter()
+        .filter(|revision| revision["field_name"] == "credit_limit")
+        .map(|revision| {
+            (
+                revision["old_value"].as_str().unwrap(),
+                revision["new_value"].as_str().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(limits.len(), 2);
+    assert!(limits.contains(&("", "500.00")) && limits.contains(&("500.00", "")));
+}
+
+#[tokio::test]
+async fn unchanged_updates_write_no_revisions() {
+    let app = TestApp::new().await;
+    let token = app.user("owner").await;
+    let card_id = app.card(&token, "Everyday").await;
+
+    patch(&app, &token, json!({ "card_id": card_id })).await;
+    patch(
+        &app,
+        &token,
+        json!({
+            "card_id": card_id,
+            "card_name": "Everyday",
+            "card_primary_color": [10, 20, 30],
+        }),
+    )
+    .await;
+    assert!(revisions(&app, &token, &card_id).await.is_empty());
+}
+
+#[tokio::test]
+async fn revisions_group_each_update_newest_first() {
+    let app = TestApp::new().await;
+    let token = app.user("owner").await;
+    let card_id = app.card(&token, "Everyday").await;
+
+    patch(
+        &app,
+        &token,
+        json!({
+            "card_id": card_id,
+            "card_name": "Groceries",
+            "card_bank": "Other Bank",
+        }),
+    )
+    .await;
+    // Revisions are ordered by second; keep the two updates apart.
+    sqlx::query!("UPDATE card_revisions SET changed_at = datetime(changed_at, '-1 minute')")
+        .execute(&app.db)
+        .await
+        .unwrap();
+    patch(
+        &app,
+        &token,
+        json!({ "card_id": card_id, "card_primary_color": [90, 20, 30] }),
+    )
+    .await;
+
+    let revisions = revisions(&app, &token, &card_id).await;
+    let fields: Vec<&str> = revisions
+        .iter()
+        .map(|revision| revision["field_name"].as_str().unwrap())
+        .collect();
+    assert_eq!(fields, ["card_primary_color", "card_bank", "card_name"]);
+    assert_eq!(revisions[0]["old_value"], "#0a141e");
+    assert_eq!(revisions[0]["new_value"], "#5a141e");
+    assert_eq!(revisions[1]["old_value"], "Test Bank");
+    assert_eq!(revisions[1]["new_value"], "Other Bank");
+    // Fields changed together share a revision.
+    assert_eq!(revisions[1]["revision_id"], revisions[2]["revision_id"]);
+    assert_ne!(revisions[0]["revision_id"], revisions[1]["revision_id"]);
+}
@@ -0,0 +1,125 @@
+//! Converting the per-currency total due into the user's home currency.
+
+use axum::http::{Method, StatusCode};
+use serde_json::{json, Value};
+
+use super::TestApp;
+
+/// A card in `currency` owing `amount_due`.
+async fn card_owing(app: &TestApp, token: &str, currency: &str, amount_due: f64) {
+    let (status, body) = app
+        .post(
+            "/card/create",
+            token,
+            json!({
+                "card_name": format!("{} card", currency),
+                "card_bank": "Test Bank",
+                "card_primary_color": [10, 20, 30],
+                "card_secondary_color": [40, 50, 60],
+                "currency": currency,
+            }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let (status, body) = app
+        .post(
+            "/card/insert_transaction",
+            token,
+            json!({ "card_id": body["card_id"], "amount_due": amount_due }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+}
+
+async fn set_rate(app: &TestApp, admin: &str, base: &str, quote: &str, rate: f64) {
+    let (status, body) = app
+        .post(
+            "/fx/rates/set",
+            admin,
+            json!({
+                "base_currency": base,
+                "quote_currency": quote,
+                "rate": rate,
+                "as_of": "2026-01-15",
+            }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+}
+
+async fn total(app: &TestApp, token: &str) -> Value {
+    let (status, body) = app
+        .request(Method::GET, "/card/total", token, &[], None)
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    body
+}
+
+fn subtotal<'a>(total: &'a Value, currency: &str) -> &'a Value {
+    total["currencies"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|subtotal| subtotal["currency"] == currency)
+        .unwrap_or_else(|| panic!("no {} subtotal in {}", currency, total))
+}
+
+#[tokio::test]
+async fn totals_convert_with_direct_and_reverse_rates() {
+    let app = TestApp::new().await;
+    let admin = app.admin("admin").await;
+    let token = app.user("owner").await;
+    let (status, body) = app
+        .post("/user/home_currency", &token, json!({ "currency": "EUR" }))
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+
+    card_owing(&app, &token, "EUR", 10.0).await;
+    card_owing(&app, &token, "USD", 100.0).await;
+    card_owing(&app, &token, "GBP", 50.0).await;
+    // 1 USD = 0.9 EUR, stored that way round.
+    set_rate(&app, &admin, "USD", "EUR", 0.9).await;
+    // Only 1 EUR = 0.8 GBP is on file, so 1 GBP = 1.25 EUR.
+    set_rate(&app, &admin, "EUR", "GBP", 0.8).await;
+
+    let total = total(&app, &token).await;
+    assert_eq!(total["home_currency"], "EUR");
+    assert_eq!(total["complete"], true);
+    assert_eq!(total["total_due"], 162.5);
+
+    let eur = subtotal(&total, "EUR");
+    assert_eq!(eur["rate"], 1.0);
+    assert_eq!(eur["converted_total_due"], 10.0);
+    let usd = subtotal(&total, "USD");
+    assert_eq!(usd["rate"], 0.9);
+    assert_eq!(usd["rate_as_of"], "2026-01-15");
+    assert_eq!(usd["converted_total_due"], 90.0);
+    let gbp = subtotal(&total, "GBP");
+    assert_eq!(gbp["rate"], 1.25);
+    assert_eq!(gbp["converted_total_due"], 62.5);
+}
+
+#[tokio::test]
+async fn missing_rates_leave_the_total_incomplete() {
+    let app = TestApp::new().await;
+    let admin = app.admin("admin").await;
+    let token = app.user("owner").await;
+    let (status, body) = app
+        .post("/user/home_currency", &token, json!({ "currency": "EUR" }))
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+
+    card_owing(&app, &token, "USD", 100.0).await;
+    card_owing(&app, &token, "CHF", 40.0).await;
+    set_rate(&app, &admin, "USD", "EUR", 0.9).await;
+
+    let total = total(&app, &token).await;
+    assert_eq!(total["complete"], false);
+    // Only what could be converted.
+    assert_eq!(total["total_due"], 90.0);
+    let chf = subtotal(&total, "CHF");
+    assert_eq!(chf["total_due"], 40.0);
+    assert!(chf["rate"].is_null());
+    assert!(chf["rate_as_of"].is_null());
+    assert!(chf["converted_total_due"].is_null());
+}
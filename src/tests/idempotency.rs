@@ -0,0 +1,169 @@
+//! `Idempotency-Key` on `insert_transaction`: retries replay, reused keys are
+//! checked against the body, and only one request per key records anything.
+
+use axum::http::{Method, StatusCode};
+use serde_json::{json, Value};
+
+use super::TestApp;
+use crate::models::InsertTransactionPayload;
+
+async fn insert(app: &TestApp, token: &str, key: &str, body: Value) -> (StatusCode, Value) {
+    app.request(
+        Method::POST,
+        "/card/insert_transaction",
+        token,
+        &[("idempotency-key", key)],
+        Some(body),
+    )
+    .await
+}
+
+async fn event_count(app: &TestApp, card_id: &str) -> i64 {
+    sqlx::query_scalar!(
+        r#"SELECT COUNT(*) as "count!: i64" FROM card_events WHERE card_id = ?"#,
+        card_id
+    )
+    .fetch_one(&app.db)
+    .await
+    .unwrap()
+}
+
+#[tokio::test]
+async fn retries_replay_the_first_response() {
+    let app = TestApp::new().await;
+    let token = app.user("retrier").await;
+    let card_id = app.card(&token, "Card").await;
+    let body = json!({ "card_id": card_id, "amount_due": 120 });
+
+    let (status, first) = insert(&app, &token, "key-1", body.clone()).await;
+    assert_eq!(status, StatusCode::OK, "{}", first);
+    let (status, retry) = insert(&app, &token, "key-1", body).await;
+    assert_eq!(status, StatusCode::OK, "{}", retry);
+    assert_eq!(retry, first);
+    assert_eq!(event_count(&app, &card_id).await, 1);
+
+    // A new key is a new transaction.
+    let (status, body) = insert(
+        &app,
+        &token,
+        "key-2",
+        json!({ "card_id": card_id, "amount_due": 150 }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_ne!(body["transaction_id"], first["transaction_id"]);
+    assert_eq!(event_count(&app, &card_id).await, 2);
+}
+
+#[tokio::test]
+async fn reused_key_with_a_different_body_is_422() {
+    let app = TestApp::new().await;
+    let token = app.user("retrier").await;
+    let card_id = app.card(&token, "Card").await;
+
+    let (status, body) = insert(
+        &app,
+        &token,
+        "key-1",
+        json!({ "card_id": card_id, "amount_due": 120 }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let (status, body) = insert(
+        &app,
+        &token,
+        "key-1",
+        json!({ "card_id": card_id, "amount_due": 130 }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY, "{}", body);
+    assert_eq!(event_count(&app, &card_id).await, 1);
+}
+
+#[tokio::test]
+async fn key_still_in_flight_is_409() {
+    let app = TestApp::new().await;
+    let token = app.user("retrier").await;
+    let card_id = app.card(&token, "Card").await;
+    let body = json!({ "card_id": card_id, "amount_due": 120 });
+
+    // Held by a request that has reserved the key but not finished yet.
+    let payload: InsertTransactionPayload = serde_json::from_value(body.clone()).unwrap();
+    let request = serde_json::to_string(&payload).unwrap();
+    sqlx::query!(
+        "INSERT INTO idempotency_keys (user_id, idempotency_key, request, expires_at)
+         VALUES ('retrier', 'key-1', ?, datetime('now', '+60 seconds'))",
+        request
+    )
+    .execute(&app.db)
+    .await
+    .unwrap();
+
+    let (status, response) = insert(&app, &token, "key-1", body).await;
+    assert_eq!(status, StatusCode::CONFLICT, "{}", response);
+    assert_eq!(event_count(&app, &card_id).await, 0);
+}
+
+#[tokio::test]
+async fn concurrent_retries_record_once() {
+    let app = TestApp::new().await;
+    let token = app.user("retrier").await;
+    let card_id = app.card(&token, "Card").await;
+    let body = json!({ "card_id": card_id, "amount_due": 120 });
+
+    let (a, b, c) = tokio::join!(
+        insert(&app, &token, "key-1", body.clone()),
+        insert(&app, &token, "key-1", body.clone()),
+        insert(&app, &token, "key-1", body.clone()),
+    );
+    let responses = [a, b, c];
+    let recorded: Vec<&Value> = responses
+        .iter()
+        .filter(|(status, _)| *status == StatusCode::OK)
+        .map(|(_, body)| body)
+        .collect();
+    assert!(!recorded.is_empty(), "{:?}", responses);
+    assert!(
+        recorded
+            .iter()
+            .all(|body| body["transaction_id"] == recorded[0]["transaction_id"]),
+        "{:?}",
+        responses
+    );
+    assert!(
+        responses
+            .iter()
+            .all(|(status, _)| *status == StatusCode::OK || *status == StatusCode::CONFLICT),
+        "{:?}",
+        responses
+    );
+    assert_eq!(event_count(&app, &card_id).await, 1);
+
+    // Once the first one finished, a retry replays it.
+    let (status, retry) = insert(&app, &token, "key-1", body).await;
+    assert_eq!(status, StatusCode::OK, "{}", retry);
+    assert_eq!(retry["transaction_id"], recorded[0]["transaction_id"]);
+}
+
+#[tokio::test]
+async fn failed_requests_release_the_key() {
+    let app = TestApp::new().await;
+    let token = app.user("retrier").await;
+    let card_id = app.card(&token, "Card").await;
+    let body = json!({ "card_id": card_id, "amount_due": 120 });
+
+    let (status, response) = app
+        .post("/card/archive", &token, json!({ "card_id": card_id }))
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", response);
+    let (status, response) = insert(&app, &token, "key-1", body.clone()).await;
+    assert_eq!(status, StatusCode::CONFLICT, "{}", response);
+
+    let (status, response) = app
+        .post("/card/unarchive", &token, json!({ "card_id": card_id }))
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", response);
+    let (status, response) = insert(&app, &token, "key-1", body).await;
+    assert_eq!(status, StatusCode::OK, "{}", response);
+    assert_eq!(event_count(&app, &card_id).await, 1);
+}
@@ -0,0 +1,85 @@
+//! Linking cards to the issuer catalog by their free-text bank name.
+
+use axum::http::StatusCode;
+use serde_json::json;
+
+use super::TestApp;
+
+#[tokio::test]
+async fn new_cards_take_the_catalog_colors() {
+    let app = TestApp::new().await;
+    let token = app.user("owner").await;
+
+    let (status, body) = app
+        .post(
+            "/card/create",
+            &token,
+            json!({ "card_name": "Everyday", "card_bank": "h.d.f.c bank" }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let card_id = body["card_id"].as_str().unwrap();
+
+    let (status, card) = app
+        .post("/card/get_card", &token, json!({ "card_id": card_id }))
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", card);
+    assert_eq!(card["card_bank"], "h.d.f.c bank");
+    assert_eq!(card["issuer_id"], "hdfc");
+    assert_eq!(card["card_primary_color"], json!([0x00, 0x4C, 0x8F]));
+    assert_eq!(card["card_secondary_color"], json!([0xED, 0x23, 0x2A]));
+
+    // A bank outside the catalog has nothing to fall back on.
+    let (status, body) = app
+        .post(
+            "/card/create",
+            &token,
+            json!({ "card_name": "Other", "card_bank": "Test Bank" }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST, "{}", body);
+}
+
+#[tokio::test]
+async fn new_aliases_relink_cards_on_file() {
+    let app = TestApp::new().await;
+    let admin = app.admin("admin").await;
+    let token = app.user("owner").await;
+    let card_id = app.card(&token, "Local card").await;
+
+    let get_card = || app.post("/card/get_card", &token, json!({ "card_id": card_id }));
+    let (_, card) = get_card().await;
+    assert!(card["issuer_id"].is_null(), "{}", card);
+
+    let (status, issuer) = app
+        .post(
+            "/issuers/create",
+            &admin,
+            json!({
+                "name": "Testing Bank",
+                "aliases": ["Test Bank"],
+                "primary_color": [10, 20, 30],
+                "secondary_color": [40, 50, 60],
+            }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", issuer);
+    let issuer_id = issuer["issuer_id"].as_str().unwrap();
+
+    let (_, card) = get_card().await;
+    assert_eq!(card["issuer_id"], issuer_id);
+    // The card keeps the colors it was created with.
+    assert_eq!(card["card_primary_color"], json!([10, 20, 30]));
+
+    // Dropping the alias releases it again.
+    let (status, body) = app
+        .post(
+            "/issuers/update",
+            &admin,
+            json!({ "issuer_id": issuer_id, "aliases": [] }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let (_, card) = get_card().await;
+    assert!(card["issuer_id"].is_null(), "{}", card);
+}
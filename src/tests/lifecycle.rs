@@ -0,0 +1,164 @@
+//! Replacing a card and carrying its balance over.
+
+use axum::http::{Method, StatusCode};
+use serde_json::json;
+
+use super::TestApp;
+use crate::{handlers::card_event, money::Money};
+
+#[tokio::test]
+async fn an_inherited_balance_is_an_opening_adjustment() {
+    let app = TestApp::new().await;
+    let token = app.user("owner").await;
+    let old_card = app.card(&token, "Old card").await;
+    let new_card = app.card(&token, "New card").await;
+
+    for amount_due in [100, 180] {
+        let (status, body) = app
+            .post(
+                "/card/insert_transaction",
+                &token,
+                json!({ "card_id": old_card, "amount_due": amount_due }),
+            )
+            .await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+    }
+
+    let (status, body) = app
+        .post(
+            "/card/replace",
+            &token,
+            json!({ "card_id": old_card, "replacement_card_id": new_card }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["card_id"], new_card.as_str());
+    assert_eq!(body["last_total_due"], json!(180.0));
+    // The old card's last delta was already moved; nothing new is.
+    assert_eq!(body["last_delta"], json!(0.0));
+
+    let events = sqlx::query!(
+        r#"SELECT event_type, amount as "amount: Money", total_due_input as "total_due_input: Money"
+         FROM card_events WHERE card_id = ?"#,
+        new_card
+    )
+    .fetch_all(&app.db)
+    .await
+    .unwrap();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].event_type, "adjustment");
+    assert_eq!(events[0].amount, Some(Money::from_minor(18_000)));
+    assert_eq!(events[0].total_due_input, Money::from_minor(18_000));
+
+    // Replaying the replacement's events gives back what was stored.
+    let (checked, mismatches) = card_event::rebuild_running_states(&app.db, None, true)
+        .await
+        .unwrap();
+    assert_eq!(checked, 2);
+    assert!(mismatches.is_empty());
+
+    // Later events build on the inherited balance.
+    let (status, body) = app
+        .post(
+            "/card/insert_transaction",
+            &token,
+            json!({ "card_id": new_card, "amount_due": 200 }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["amount_due"], json!(20.0));
+}
+
+#[tokio::test]
+async fn a_replacement_without_inheritance_starts_empty() {
+    let app = TestApp::new().await;
+    let token = app.user("owner").await;
+    let old_card = app.card(&token, "Old card").await;
+    let new_card = app.card(&token, "New card").await;
+
+    let (status, body) = app
+        .post(
+            "/card/insert_transaction",
+            &token,
+            json!({ "card_id": old_card, "amount_due": 100 }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+
+    let (status, body) = app
+        .post(
+            "/card/replace",
+            &token,
+            json!({
+                "card_id": old_card,
+                "replacement_card_id": new_card,
+                "inherit_balance": false,
+            }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["last_total_due"], json!(0.0));
+
+    let count = sqlx::query_scalar!(
+        "SELECT COUNT(*) FROM card_events WHERE card_id = ?",
+        new_card
+    )
+    .fetch_one(&app.db)
+    .await
+    .unwrap();
+    assert_eq!(count, 0);
+}
+
+#[tokio::test]
+async fn replaced_cards_drop_out_of_totals() {
+    let app = TestApp::new().await;
+    let token = app.user("owner").await;
+    let old_card = app.card(&token, "Old card").await;
+    let new_card = app.card(&token, "New card").await;
+
+    let (status, body) = app
+        .post("/card/tags/create", &token, json!({ "name": "travel" }))
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let tag_id = body["tag_id"].as_str().unwrap().to_string();
+    let (status, body) = app
+        .post(
+            "/card/tags/assign",
+            &token,
+            json!({ "card_id": old_card, "tag_ids": [tag_id] }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let (status, body) = app
+        .post(
+            "/card/insert_transaction",
+            &token,
+            json!({ "card_id": old_card, "amount_due": 100 }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+
+    let (status, body) = app
+        .post(
+            "/card/replace",
+            &token,
+            json!({ "card_id": old_card, "replacement_card_id": new_card }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+
+    // Counted once, on the replacement.
+    let (status, total) = app
+        .request(Method::GET, "/card/total", &token, &[], None)
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", total);
+    assert_eq!(total["total_due"], json!(100.0));
+
+    // The untagged replacement doesn't pick up the old card's tag.
+    let (status, tags) = app
+        .request(Method::GET, "/card/tags", &token, &[], None)
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", tags);
+    assert_eq!(tags[0]["card_count"], 0);
+    assert_eq!(tags[0]["totals"], json!([]));
+}
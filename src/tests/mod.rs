@@ -0,0 +1,163 @@
+//! Router-level tests: the real app on a fresh in-memory database.
+
+mod card_access;
+mod card_list;
+mod card_update;
+mod currency;
+mod idempotency;
+mod issuers;
+mod lifecycle;
+mod statements;
+mod tags;
+mod transactions;
+
+use std::{str::FromStr, sync::Arc};
+
+use axum::{
+    body::{to_bytes, Body, Bytes},
+    http::{header, Method, Request, StatusCode},
+    Router,
+};
+use rusty_paseto::{
+    core::{Local, V4},
+    prelude::{Key, PasetoSymmetricKey},
+};
+use serde_json::Value;
+use sqlx::{
+    sqlite::{SqliteConnectOptions, SqlitePoolOptions},
+    SqlitePool,
+};
+use time::{Duration, OffsetDateTime};
+use tower::ServiceExt;
+
+use crate::{app, handlers::common::get_paseto_token, models::AppState, money};
+
+pub struct TestApp {
+    pub db: SqlitePool,
+    state: AppState,
+    router: Router,
+}
+
+impl TestApp {
+    pub async fn new() -> Self {
+        // Every connection to `:memory:` is its own database, so keep just one.
+        let db = SqlitePoolOptions::new()
+            .max_connections(1)
+            .idle_timeout(None)
+            .max_lifetime(None)
+            .connect_with(SqliteConnectOptions::from_str("sqlite::memory:").unwrap())
+            .await
+            .unwrap();
+        sqlx::migrate!().run(&db).await.unwrap();
+
+        let state = AppState {
+            db: db.clone(),
+            redis: None,
+            paseto_key: Arc::new(PasetoSymmetricKey::<V4, Local>::from(Key::from(
+                [7u8; 32].as_slice(),
+            ))),
+            trash_retention_days: 30,
+            max_total_due: money::DEFAULT_MAX_TOTAL_DUE,
+        };
+        let router = app::build_router(state.clone());
+        TestApp { db, state, router }
+    }
+
+    /// Creates a user and returns a bearer token for it.
+    pub async fn user(&self, user_id: &str) -> String {
+        self.account(user_id, "user").await
+    }
+
+    /// Like `user`, for an admin.
+    pub async fn admin(&self, user_id: &str) -> String {
+        self.account(user_id, "admin").await
+    }
+
+    async fn account(&self, user_id: &str, role: &str) -> String {
+        sqlx::query!(
+            "INSERT INTO users (user_id, user_name, user_password, user_role) VALUES (?, ?, '', ?)",
+            user_id,
+            user_id,
+            role
+        )
+        .execute(&self.db)
+        .await
+        .unwrap();
+        get_paseto_token(
+            user_id,
+            role.to_string(),
+            &self.state.paseto_key,
+            OffsetDateTime::now_utc() + Duration::hours(1),
+        )
+        .unwrap_or_else(|e| panic!("{}", e.1))
+    }
+
+    pub async fn request(
+        &self,
+        method: Method,
+        path: &str,
+        token: &str,
+        headers: &[(&str, &str)],
+        body: Option<Value>,
+    ) -> (StatusCode, Value) {
+        let (status, bytes) = self.send(method, path, token, headers, body).await;
+        let body = serde_json::from_slice(&bytes)
+            .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(&bytes).into_owned()));
+        (status, body)
+    }
+
+    /// Like `request`, but returns the raw response body.
+    pub async fn send(
+        &self,
+        method: Method,
+        path: &str,
+        token: &str,
+        headers: &[(&str, &str)],
+        body: Option<Value>,
+    ) -> (StatusCode, Bytes) {
+        let mut request = Request::builder()
+            .method(method)
+            .uri(path)
+            .header(header::AUTHORIZATION, format!("Bearer {}", token));
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        let request = match body {
+            Some(body) => request
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body.to_string())),
+            None => request.body(Body::empty()),
+        }
+        .unwrap();
+
+        let response = self.router.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        (
+            status,
+            to_bytes(response.into_body(), usize::MAX).await.unwrap(),
+        )
+    }
+
+    pub async fn post(&self, path: &str, token: &str, body: Value) -> (StatusCode, Value) {
+        self.request(Method::POST, path, token, &[], Some(body))
+            .await
+    }
+
+    /// Creates a card for the token's user and returns its id.
+    pub async fn card(&self, token: &str, name: &str) -> String {
+        let (status, body) = self
+            .post(
+                "/card/create",
+                token,
+                serde_json::json!({
+                    "card_name": name,
+                    "card_bank": "Test Bank",
+                    "card_primary_color": [10, 20, 30],
+                    "card_secondary_color": [40, 50, 60],
+                }),
+            )
+            .await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        body["card_id"].as_str().unwrap().to_string()
+    }
+}
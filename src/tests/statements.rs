@@ -0,0 +1,297 @@
+//! Statement closing, on demand and on schedule, and payment tracking.
+
+use axum::http::StatusCode;
+use serde_json::json;
+use time::{Duration, OffsetDateTime};
+
+use super::TestApp;
+use crate::{handlers::statement::close_due_statements, money::Money};
+
+#[tokio::test]
+async fn missed_statement_dates_are_all_closed() {
+    let app = TestApp::new().await;
+    let owner = app.user("owner").await;
+    let card_id = app.card(&owner, "Card").await;
+    let closed_card_id = app.card(&owner, "Closed card").await;
+    for card in [&card_id, &closed_card_id] {
+        let (status, body) = app
+            .request(
+                axum::http::Method::PATCH,
+                "/card/update",
+                &owner,
+                &[],
+                Some(json!({ "card_id": card, "statement_day": 1, "payment_due_day": 20 })),
+            )
+            .await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+    }
+
+    let today = OffsetDateTime::now_utc().date();
+    let first = today - Duration::days(70);
+    let second = today - Duration::days(35);
+    for (card, body, date) in [
+        (
+            &card_id,
+            json!({ "card_id": card_id, "amount_due": 100 }),
+            first,
+        ),
+        (
+            &card_id,
+            json!({ "card_id": card_id, "event_type": "purchase", "amount": 50, "merchant": "Shop" }),
+            second,
+        ),
+        (
+            &closed_card_id,
+            json!({ "card_id": closed_card_id, "amount_due": 10 }),
+            first,
+        ),
+    ] {
+        let (status, response) = app.post("/card/insert_transaction", &owner, body).await;
+        assert_eq!(status, StatusCode::OK, "{}", response);
+        let timestamp = format!("{} 12:00:00", date);
+        let transaction_id = response["transaction_id"].as_str().unwrap();
+        sqlx::query!(
+            "UPDATE card_events SET timestamp = ? WHERE transaction_id = ? AND card_id = ?",
+            timestamp,
+            transaction_id,
+            card
+        )
+        .execute(&app.db)
+        .await
+        .unwrap();
+    }
+    sqlx::query!(
+        "UPDATE cards SET status = 'closed' WHERE card_id = ?",
+        closed_card_id
+    )
+    .execute(&app.db)
+    .await
+    .unwrap();
+
+    let expected: Vec<_> = (0..=70)
+        .map(|days| first + Duration::days(days))
+        .filter(|date| date.day() == 1 && *date <= today)
+        .collect();
+    assert_eq!(
+        close_due_statements(&app.db).await.unwrap(),
+        expected.len() as u64
+    );
+    // Nothing left to close.
+    assert_eq!(close_due_statements(&app.db).await.unwrap(), 0);
+
+    let statements = sqlx::query!(
+        r#"SELECT date(cycle_end) as "statement_date!: String", due_date as "due_date!: String",
+                closing_total_due as "closing_total_due!: Money",
+                (SELECT COUNT(*) FROM card_statement_events e
+                 WHERE e.statement_id = s.statement_id) as "events!: i64"
+         FROM card_statements s WHERE card_id = ? ORDER BY cycle_end"#,
+        card_id
+    )
+    .fetch_all(&app.db)
+    .await
+    .unwrap();
+    assert_eq!(statements.len(), expected.len());
+    for (statement, date) in statements.iter().zip(&expected) {
+        assert_eq!(statement.statement_date, date.to_string());
+        // Due on the 20th of the statement's own month, not of today's.
+        assert_eq!(
+            statement.due_date,
+            date.replace_day(20).unwrap().to_string()
+        );
+        let closing = if *date >= second { 150 } else { 100 };
+        assert_eq!(
+            statement.closing_total_due,
+            Money::from_minor(closing * 100)
+        );
+    }
+    assert_eq!(statements.iter().map(|s| s.events).sum::<i64>(), 2);
+
+    let closed_card_statements = sqlx::query_scalar!(
+        r#"SELECT COUNT(*) as "count!: i64" FROM card_statements WHERE card_id = ?"#,
+        closed_card_id
+    )
+    .fetch_one(&app.db)
+    .await
+    .unwrap();
+    assert_eq!(closed_card_statements, 0);
+}
+
+/// A card with one closed statement of 120.00; returns the card and statement ids.
+async fn closed_statement(app: &TestApp, token: &str) -> (String, String) {
+    let card_id = app.card(token, "Card").await;
+    let (status, body) = app
+        .post(
+            "/card/insert_transaction",
+            token,
+            json!({ "card_id": card_id, "amount_due": 120 }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let (status, body) = app
+        .post(
+            "/card/statements/close",
+            token,
+            json!({ "card_id": card_id }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["closing_total_due"], 120.0);
+    let statement_id = body["statement_id"].as_str().unwrap().to_string();
+    (card_id, statement_id)
+}
+
+async fn pay(
+    app: &TestApp,
+    token: &str,
+    card_id: &str,
+    statement_id: &str,
+    payment: serde_json::Value,
+) -> (StatusCode, serde_json::Value) {
+    let mut body = json!({ "card_id": card_id, "statement_id": statement_id });
+    body.as_object_mut()
+        .unwrap()
+        .extend(payment.as_object().unwrap().clone());
+    app.post("/card/statements/payment", token, body).await
+}
+
+#[tokio::test]
+async fn paid_statements_cover_the_total() {
+    let app = TestApp::new().await;
+    let owner = app.user("owner").await;
+    let (card_id, statement_id) = closed_statement(&app, &owner).await;
+
+    let (status, body) = pay(
+        &app,
+        &owner,
+        &card_id,
+        &statement_id,
+        json!({ "payment_status": "paid" }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["payment_status"], "paid");
+    assert_eq!(body["amount_paid"], 120.0);
+    assert!(body["paid_at"].is_string());
+
+    for short in [0, 50] {
+        let (status, body) = pay(
+            &app,
+            &owner,
+            &card_id,
+            &statement_id,
+            json!({ "payment_status": "paid", "amount_paid": short }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", body);
+    }
+
+    let (status, body) = pay(
+        &app,
+        &owner,
+        &card_id,
+        &statement_id,
+        json!({ "payment_status": "paid", "amount_paid": 125 }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["amount_paid"], 125.0);
+}
+
+#[tokio::test]
+async fn partial_payments_fall_between_zero_and_the_total() {
+    let app = TestApp::new().await;
+    let owner = app.user("owner").await;
+    let (card_id, statement_id) = closed_statement(&app, &owner).await;
+
+    for amount in [json!(null), json!(0), json!(120), json!(130)] {
+        let (status, body) = pay(
+            &app,
+            &owner,
+            &card_id,
+            &statement_id,
+            json!({ "payment_status": "partially_paid", "amount_paid": amount }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{} {}", amount, body);
+    }
+
+    let (status, body) = pay(
+        &app,
+        &owner,
+        &card_id,
+        &statement_id,
+        json!({ "payment_status": "partially_paid", "amount_paid": 40.5 }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["payment_status"], "partially_paid");
+    assert_eq!(body["amount_paid"], 40.5);
+}
+
+#[tokio::test]
+async fn unpaid_clears_the_payment() {
+    let app = TestApp::new().await;
+    let owner = app.user("owner").await;
+    let (card_id, statement_id) = closed_statement(&app, &owner).await;
+
+    let (status, body) = pay(
+        &app,
+        &owner,
+        &card_id,
+        &statement_id,
+        json!({ "payment_status": "paid" }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+
+    let (status, body) = pay(
+        &app,
+        &owner,
+        &card_id,
+        &statement_id,
+        json!({ "payment_status": "unpaid", "amount_paid": 30 }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["payment_status"], "unpaid");
+    assert_eq!(body["amount_paid"], 0.0);
+    assert!(body["paid_at"].is_null());
+}
+
+#[tokio::test]
+async fn archived_and_closed_cards_cannot_close_statements() {
+    let app = TestApp::new().await;
+    let owner = app.user("owner").await;
+    let archived = app.card(&owner, "Archived").await;
+    let closed = app.card(&owner, "Closed").await;
+
+    let (status, body) = app
+        .post("/card/archive", &owner, json!({ "card_id": archived }))
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let (status, body) = app
+        .post(
+            "/card/status",
+            &owner,
+            json!({ "card_id": closed, "status": "closed" }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+
+    for card_id in [&archived, &closed] {
+        let (status, body) = app
+            .post(
+                "/card/statements/close",
+                &owner,
+                json!({ "card_id": card_id }),
+            )
+            .await;
+        assert_eq!(status, StatusCode::CONFLICT, "{}", body);
+    }
+    let statements =
+        sqlx::query_scalar!(r#"SELECT COUNT(*) as "count!: i64" FROM card_statements"#)
+            .fetch_one(&app.db)
+            .await
+            .unwrap();
+    assert_eq!(statements, 0);
+}
@@ -0,0 +1,100 @@
+//! Per-tag card counts and totals.
+
+use axum::http::{Method, StatusCode};
+use serde_json::{json, Value};
+
+use super::TestApp;
+
+async fn tag(app: &TestApp, token: &str, name: &str) -> String {
+    let (status, body) = app
+        .post("/card/tags/create", token, json!({ "name": name }))
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    body["tag_id"].as_str().unwrap().to_string()
+}
+
+/// A tagged card in `currency` owing `amount_due`; returns its id.
+async fn tagged_card(
+    app: &TestApp,
+    token: &str,
+    currency: &str,
+    amount_due: f64,
+    tag_ids: &[&str],
+) -> String {
+    let card_id = app.card(token, "Card").await;
+    let (status, body) = app
+        .request(
+            Method::PATCH,
+            "/card/update",
+            token,
+            &[],
+            Some(json!({ "card_id": card_id, "currency": currency })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let (status, body) = app
+        .post(
+            "/card/insert_transaction",
+            token,
+            json!({ "card_id": card_id, "amount_due": amount_due }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let (status, body) = app
+        .post(
+            "/card/tags/assign",
+            token,
+            json!({ "card_id": card_id, "tag_ids": tag_ids }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    card_id
+}
+
+async fn tags(app: &TestApp, token: &str) -> Vec<Value> {
+    let (status, body) = app
+        .request(Method::GET, "/card/tags", token, &[], None)
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    body.as_array().unwrap().clone()
+}
+
+#[tokio::test]
+async fn tags_total_their_active_cards_per_currency() {
+    let app = TestApp::new().await;
+    let token = app.user("owner").await;
+    let travel = tag(&app, &token, "travel").await;
+    let work = tag(&app, &token, "Work").await;
+    tag(&app, &token, "empty").await;
+
+    tagged_card(&app, &token, "USD", 100.0, &[&travel, &work]).await;
+    tagged_card(&app, &token, "USD", 25.5, &[&travel]).await;
+    tagged_card(&app, &token, "EUR", 40.0, &[&travel]).await;
+    let archived = tagged_card(&app, &token, "USD", 500.0, &[&work]).await;
+    let (status, body) = app
+        .post("/card/archive", &token, json!({ "card_id": archived }))
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+
+    let tags = tags(&app, &token).await;
+    let names: Vec<&str> = tags
+        .iter()
+        .map(|tag| tag["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, ["empty", "travel", "Work"]);
+
+    assert_eq!(tags[0]["card_count"], 0);
+    assert_eq!(tags[0]["totals"], json!([]));
+
+    assert_eq!(tags[1]["card_count"], 3);
+    let totals = tags[1]["totals"].as_array().unwrap();
+    assert_eq!(totals.len(), 2);
+    assert_eq!(totals[0]["currency"], "EUR");
+    assert_eq!(totals[0]["last_total_due"], 40.0);
+    assert_eq!(totals[1]["currency"], "USD");
+    assert_eq!(totals[1]["last_total_due"], 125.5);
+
+    // The archived card is left out.
+    assert_eq!(tags[2]["card_count"], 1);
+    assert_eq!(tags[2]["totals"][0]["last_total_due"], 100.0);
+}
@@ -0,0 +1,185 @@
+//! Editing and deleting single transactions.
+
+use axum::http::StatusCode;
+use serde_json::{json, Value};
+
+use super::TestApp;
+
+async fn insert(app: &TestApp, token: &str, body: Value) -> String {
+    let (status, body) = app.post("/card/insert_transaction", token, body).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    body["transaction_id"].as_str().unwrap().to_string()
+}
+
+async fn total_due(app: &TestApp, token: &str, card_id: &str) -> Value {
+    let (status, body) = app
+        .post("/card/get_card", token, json!({ "card_id": card_id }))
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    body["last_total_due"].clone()
+}
+
+#[tokio::test]
+async fn edits_cant_leave_a_snapshot_below_the_total_before_it() {
+    let app = TestApp::new().await;
+    let token = app.user("owner").await;
+    let card_id = app.card(&token, "Card").await;
+
+    insert(
+        &app,
+        &token,
+        json!({ "card_id": card_id, "amount_due": 100 }),
+    )
+    .await;
+    let payment = insert(
+        &app,
+        &token,
+        json!({ "card_id": card_id, "event_type": "payment", "amount": 30 }),
+    )
+    .await;
+    let snapshot = insert(
+        &app,
+        &token,
+        json!({ "card_id": card_id, "amount_due": 90 }),
+    )
+    .await;
+    let before = total_due(&app, &token, &card_id).await;
+
+    // 90 after 100 only works because of the payment in between.
+    let (status, body) = app
+        .post(
+            "/card/delete_transaction",
+            &token,
+            json!({ "card_id": card_id, "transaction_id": payment }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY, "{}", body);
+
+    let (status, body) = app
+        .post(
+            "/card/update_transaction",
+            &token,
+            json!({ "card_id": card_id, "transaction_id": snapshot, "amount": 60 }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY, "{}", body);
+
+    let (status, body) = app
+        .post(
+            "/card/update_transaction",
+            &token,
+            json!({ "card_id": card_id, "transaction_id": payment, "amount": 5 }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY, "{}", body);
+
+    // Nothing was changed by the rejected edits.
+    assert_eq!(total_due(&app, &token, &card_id).await, before);
+
+    let (status, body) = app
+        .post(
+            "/card/update_transaction",
+            &token,
+            json!({ "card_id": card_id, "transaction_id": snapshot, "amount": 75 }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+}
+
+#[tokio::test]
+async fn closed_statement_cycles_cant_be_edited() {
+    let app = TestApp::new().await;
+    let token = app.user("owner").await;
+    let card_id = app.card(&token, "Card").await;
+
+    let closed = insert(
+        &app,
+        &token,
+        json!({ "card_id": card_id, "amount_due": 100 }),
+    )
+    .await;
+    let (status, body) = app
+        .post(
+            "/card/statements/close",
+            &token,
+            json!({ "card_id": card_id }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    // Leave room after the cycle so a new event lands in the open one.
+    sqlx::query!(
+        "UPDATE card_statements SET cycle_end = datetime(cycle_end, '-1 hour') WHERE card_id = ?",
+        card_id
+    )
+    .execute(&app.db)
+    .await
+    .unwrap();
+    sqlx::query!(
+        "UPDATE card_events SET timestamp = datetime(timestamp, '-2 hours') WHERE transaction_id = ?",
+        closed
+    )
+    .execute(&app.db)
+    .await
+    .unwrap();
+    let open = insert(
+        &app,
+        &token,
+        json!({ "card_id": card_id, "amount_due": 120 }),
+    )
+    .await;
+
+    for (path, body) in [
+        (
+            "/card/update_transaction",
+            json!({ "card_id": card_id, "transaction_id": closed, "amount": 110 }),
+        ),
+        (
+            "/card/delete_transaction",
+            json!({ "card_id": card_id, "transaction_id": closed }),
+        ),
+        // Moving an open event back into the closed cycle.
+        (
+            "/card/update_transaction",
+            json!({ "card_id": card_id, "transaction_id": open, "timestamp": "2000-01-01" }),
+        ),
+    ] {
+        let (status, response) = app.post(path, &token, body.clone()).await;
+        assert_eq!(
+            status,
+            StatusCode::CONFLICT,
+            "{} {} {}",
+            path,
+            body,
+            response
+        );
+    }
+
+    let (status, body) = app
+        .post(
+            "/card/update_transaction",
+            &token,
+            json!({ "card_id": card_id, "transaction_id": open, "amount": 130 }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+
+    let (status, body) = app
+        .post(
+            "/card/undo_transaction",
+            &token,
+            json!({ "card_id": card_id, "transaction_id": open }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+
+    // The closed event is the latest now, but still can't be undone.
+    let (status, body) = app
+        .post(
+            "/card/undo_transaction",
+            &token,
+            json!({ "card_id": card_id, "transaction_id": closed }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CONFLICT, "{}", body);
+    assert_eq!(total_due(&app, &token, &card_id).await, json!(100.0));
+}